@@ -0,0 +1,575 @@
+//! Import of Automath AUT-68 texts.
+//!
+//! A text is a sequence of lines
+//!
+//! ```text
+//! <indicator> * <identifier> := <definiens> : <category>
+//! ```
+//!
+//! where the context indicator is `0`, `@` or empty for the empty context and
+//! otherwise the last block variable of the context. A definiens `EB` opens a
+//! block, introducing a variable of the category, and `PN` declares a
+//! primitive notion. Expressions are `TYPE` and `PROP`, both read as `*`,
+//! abstractions `[x:A]B`, applications `<a>f` and instances `f(a, ...)`,
+//! whose omitted leading arguments are the variables of the context of `f`.
+//! Comments run from `#` to the end of the line or are enclosed in `{ }`.
+//!
+//! AUT-68 uses `[x:A]B` both for functions and for their types. It is read
+//! as a product in type positions, that is in categories, in the types of
+//! bound variables, in definientia whose category is a sort, and in
+//! arguments for parameters whose type is a sort; everywhere else it is an
+//! abstraction. Identifiers of variables are mapped to distinct letters, and
+//! those of definitions that def2 would read as variables, like single
+//! letters, are renamed. An omitted argument is the block variable itself,
+//! so the context of the definition must start like the current one.
+
+use std::collections::HashSet;
+
+use crate::{
+    book::{Block, Book},
+    model::{Application, Definition, Expr, Lambda, Pi, Var, generate_free_var_gte},
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Star,
+    At,
+    Assign,
+    Colon,
+    Comma,
+    Semicolon,
+    LBracket,
+    RBracket,
+    LAngle,
+    RAngle,
+    LParen,
+    RParen,
+}
+
+fn lex(s: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+    let mut line = 1;
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '\'';
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '\n' => {
+                line += 1;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '#' => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+                continue;
+            }
+            '{' => {
+                for c in chars.by_ref() {
+                    match c {
+                        '}' => break,
+                        '\n' => line += 1,
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            '*' => Token::Star,
+            '@' => Token::At,
+            ':' if chars.next_if_eq(&'=').is_some() => Token::Assign,
+            ':' => Token::Colon,
+            ',' => Token::Comma,
+            ';' => Token::Semicolon,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            '<' => Token::LAngle,
+            '>' => Token::RAngle,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            c if is_ident(c) => {
+                let mut ident = c.to_string();
+                while let Some(c) = chars.next_if(|&c| is_ident(c)) {
+                    ident.push(c);
+                }
+                Token::Ident(ident)
+            }
+            c => return Err(format!("line {line}: unexpected character {c:?}")),
+        };
+        tokens.push((line, token));
+    }
+    Ok(tokens)
+}
+
+/// An AUT-68 expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Aut {
+    Sort,
+    Ident(String),
+    Instance(String, Vec<Aut>),
+    Abstraction(String, Box<Aut>, Box<Aut>),
+    /// `<a>f`, the argument first.
+    Application(Box<Aut>, Box<Aut>),
+}
+
+enum Definiens {
+    Block,
+    Primitive,
+    Expr(Aut),
+}
+
+struct Line {
+    lineno: usize,
+    indicator: Option<String>,
+    name: String,
+    definiens: Definiens,
+    category: Aut,
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset).map(|(_, t)| t)
+    }
+
+    fn lineno(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(l, _)| *l)
+    }
+
+    fn next(&mut self, what: &str) -> Result<Token> {
+        let token = self
+            .peek(0)
+            .cloned()
+            .ok_or_else(|| format!("unexpected end of input, expecting {what}"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<()> {
+        let lineno = self.lineno();
+        match self.next(what)? {
+            token if token == expected => Ok(()),
+            token => Err(format!("line {lineno}: expected {what}, found {token:?}")),
+        }
+    }
+
+    fn ident(&mut self, what: &str) -> Result<String> {
+        let lineno = self.lineno();
+        match self.next(what)? {
+            Token::Ident(x) => Ok(x),
+            token => Err(format!("line {lineno}: expected {what}, found {token:?}")),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Aut> {
+        let lineno = self.lineno();
+        match self.next("an expression")? {
+            Token::Ident(x) if ["TYPE", "PROP", "'type'", "'prop'"].contains(&x.as_str()) => {
+                Ok(Aut::Sort)
+            }
+            Token::Ident(x) if self.peek(0) == Some(&Token::LParen) => {
+                self.pos += 1;
+                let mut args = vec![];
+                if self.peek(0) == Some(&Token::RParen) {
+                    self.pos += 1;
+                    return Ok(Aut::Instance(x, args));
+                }
+                loop {
+                    args.push(self.expr()?);
+                    let lineno = self.lineno();
+                    match self.next(")")? {
+                        Token::Comma => {}
+                        Token::RParen => return Ok(Aut::Instance(x, args)),
+                        token => {
+                            return Err(format!("line {lineno}: expected , or ), found {token:?}"));
+                        }
+                    }
+                }
+            }
+            Token::Ident(x) => Ok(Aut::Ident(x)),
+            Token::LBracket => {
+                let x = self.ident("a variable")?;
+                self.expect(Token::Colon, ":")?;
+                let a = self.expr()?;
+                self.expect(Token::RBracket, "]")?;
+                Ok(Aut::Abstraction(x, Box::new(a), Box::new(self.expr()?)))
+            }
+            Token::LAngle => {
+                let a = self.expr()?;
+                self.expect(Token::RAngle, ">")?;
+                Ok(Aut::Application(Box::new(a), Box::new(self.expr()?)))
+            }
+            token => Err(format!(
+                "line {lineno}: expected an expression, found {token:?}"
+            )),
+        }
+    }
+
+    fn line(&mut self) -> Result<Line> {
+        let lineno = self.lineno();
+        let indicator = match self.next("a context indicator")? {
+            Token::Star => None,
+            Token::At => {
+                self.expect(Token::Star, "*")?;
+                None
+            }
+            Token::Ident(x) => {
+                self.expect(Token::Star, "*")?;
+                (x != "0").then_some(x)
+            }
+            token => {
+                return Err(format!(
+                    "line {lineno}: expected a context indicator, found {token:?}"
+                ));
+            }
+        };
+        let name = self.ident("an identifier")?;
+        self.expect(Token::Assign, ":=")?;
+        let definiens = match self.peek(0) {
+            Some(Token::Ident(x)) if ["EB", "'eb'"].contains(&x.as_str()) => {
+                self.pos += 1;
+                Definiens::Block
+            }
+            Some(Token::Ident(x)) if ["PN", "PRIM", "'prim'"].contains(&x.as_str()) => {
+                self.pos += 1;
+                Definiens::Primitive
+            }
+            _ => Definiens::Expr(self.expr()?),
+        };
+        self.expect(Token::Colon, ":")?;
+        let category = self.expr()?;
+        if self.peek(0) == Some(&Token::Semicolon) {
+            self.pos += 1;
+        }
+        Ok(Line {
+            lineno,
+            indicator,
+            name,
+            definiens,
+            category,
+        })
+    }
+}
+
+/// A block variable: its name, letter, type and enclosing block variable.
+struct Variable {
+    name: String,
+    var: Var,
+    ty: Expr,
+    parent: Option<usize>,
+}
+
+/// Whether the def2 parser reads `name` as a definition name rather than a
+/// variable.
+fn is_name(name: &str) -> bool {
+    name.len() > 1
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Default)]
+struct Import {
+    variables: Vec<Variable>,
+    blocks: Vec<Block>,
+    /// The identifier and the block variables of the context of each block.
+    defined: Vec<(String, Vec<usize>)>,
+    /// Identifiers of the text and names given to renamed definitions.
+    taken: HashSet<String>,
+}
+
+impl Import {
+    /// The block variables of the context ending in `indicator`, outermost
+    /// first.
+    fn context(&self, indicator: Option<usize>) -> Vec<usize> {
+        let mut context = vec![];
+        let mut next = indicator;
+        while let Some(i) = next {
+            context.push(i);
+            next = self.variables[i].parent;
+        }
+        context.reverse();
+        context
+    }
+
+    /// A letter for a new variable `name`, distinct from those in `scope`.
+    fn fresh(scope: &[(String, Var)], name: &str) -> Result<Var> {
+        let avoid = scope.iter().map(|(_, v)| *v).collect::<HashSet<_>>();
+        let first = name
+            .chars()
+            .next()
+            .filter(char::is_ascii_alphabetic)
+            .unwrap_or('a');
+        generate_free_var_gte(&avoid, Var(first))
+            .map_err(|_| format!("too many variables in scope to name {name}"))
+    }
+
+    /// A definition name for the identifier `name`, which is renamed if def2
+    /// would not read it as one.
+    fn rename(&mut self, name: &str) -> String {
+        if is_name(name) {
+            return name.to_string();
+        }
+        let mut base = name.replace('\'', "_");
+        if !base.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            base.insert(0, 'd');
+        }
+        let renamed = if is_name(&base) && !self.taken.contains(&base) {
+            base
+        } else {
+            (1..)
+                .map(|k| format!("{base}_{k}"))
+                .find(|x| !self.taken.contains(x))
+                .unwrap()
+        };
+        self.taken.insert(renamed.clone());
+        renamed
+    }
+
+    /// The block of the identifier `name` and the block variables of its
+    /// context.
+    fn block(&self, name: &str) -> Result<(&Block, &[usize])> {
+        self.defined
+            .iter()
+            .rposition(|(x, _)| x == name)
+            .map(|i| (&self.blocks[i], self.defined[i].1.as_slice()))
+            .ok_or_else(|| format!("unknown identifier {name}"))
+    }
+
+    /// The first `count` parameters of `name`, as used for omitted
+    /// arguments: its context must start like the current `context`.
+    fn implicit(
+        context: &[usize],
+        name: &str,
+        (block, params): (&Block, &[usize]),
+        count: usize,
+    ) -> Result<Vec<Expr>> {
+        block.context[..count]
+            .iter()
+            .zip(params)
+            .enumerate()
+            .map(|(i, ((v, _), p))| {
+                (context.get(i) == Some(p))
+                    .then_some(Expr::Var(*v))
+                    .ok_or_else(|| {
+                        format!("the context does not contain the parameter {v} of {name}")
+                    })
+            })
+            .collect()
+    }
+
+    fn expr(
+        &self,
+        context: &[usize],
+        scope: &[(String, Var)],
+        e: &Aut,
+        is_type: bool,
+    ) -> Result<Expr> {
+        Ok(match e {
+            Aut::Sort => Expr::Asterisk,
+            Aut::Ident(x) => match scope.iter().rev().find(|(y, _)| y == x) {
+                Some((_, v)) => Expr::Var(*v),
+                None => {
+                    let found = self.block(x)?;
+                    let args = Self::implicit(context, x, found, found.0.context.len())?;
+                    Definition(found.0.name.clone(), args).into()
+                }
+            },
+            Aut::Instance(f, args) => {
+                let found @ (block, _) = self.block(f)?;
+                let Some(omitted) = block.context.len().checked_sub(args.len()) else {
+                    return Err(format!(
+                        "{f} takes at most {} arguments, found {}",
+                        block.context.len(),
+                        args.len()
+                    ));
+                };
+                let mut all = Self::implicit(context, f, found, omitted)?;
+                for ((_, ty), arg) in block.context[omitted..].iter().zip(args) {
+                    all.push(self.expr(context, scope, arg, ty.is_sort())?);
+                }
+                Definition(block.name.clone(), all).into()
+            }
+            Aut::Abstraction(x, a, b) => {
+                let a = self.expr(context, scope, a, true)?;
+                let v = Self::fresh(scope, x)?;
+                let mut inner = scope.to_vec();
+                inner.push((x.clone(), v));
+                let b = self.expr(context, &inner, b, is_type)?;
+                if is_type {
+                    Pi(v, a, b).into()
+                } else {
+                    Lambda(v, a, b).into()
+                }
+            }
+            Aut::Application(a, f) => Application(
+                self.expr(context, scope, f, false)?,
+                self.expr(context, scope, a, false)?,
+            )
+            .into(),
+        })
+    }
+
+    fn line(&mut self, line: Line) -> Result<()> {
+        let indicator = match &line.indicator {
+            None => None,
+            Some(x) => Some(
+                self.variables
+                    .iter()
+                    .rposition(|v| &v.name == x)
+                    .ok_or_else(|| format!("{x} is not a block variable"))?,
+            ),
+        };
+        let context = self.context(indicator);
+        let scope = context
+            .iter()
+            .map(|&i| (self.variables[i].name.clone(), self.variables[i].var))
+            .collect::<Vec<_>>();
+        let params = context
+            .iter()
+            .map(|&i| (self.variables[i].var, self.variables[i].ty.clone()))
+            .collect::<Vec<_>>();
+
+        let n = self.expr(&context, &scope, &line.category, true)?;
+        let m = match &line.definiens {
+            Definiens::Block => {
+                let var = Self::fresh(&scope, &line.name)?;
+                self.variables.push(Variable {
+                    name: line.name,
+                    var,
+                    ty: n,
+                    parent: indicator,
+                });
+                return Ok(());
+            }
+            Definiens::Primitive => None,
+            Definiens::Expr(m) => Some(self.expr(&context, &scope, m, n.is_sort())?),
+        };
+        let name = self.rename(&line.name);
+        self.blocks.push(Block {
+            context: params,
+            name,
+            m,
+            n,
+        });
+        self.defined.push((line.name, context));
+        Ok(())
+    }
+}
+
+impl Book {
+    /// Reads a book from an Automath AUT-68 text.
+    pub fn from_automath(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: lex(s)?,
+            pos: 0,
+        };
+        let mut import = Import {
+            taken: parser
+                .tokens
+                .iter()
+                .filter_map(|(_, t)| match t {
+                    Token::Ident(x) => Some(x.clone()),
+                    _ => None,
+                })
+                .collect(),
+            ..Import::default()
+        };
+        while parser.pos < parser.tokens.len() {
+            let line = parser.line()?;
+            let lineno = line.lineno;
+            import
+                .line(line)
+                .map_err(|e| format!("line {lineno}: {e}"))?;
+        }
+        Ok(Self(import.blocks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    const IMPLICATION: &str = "\
+# propositional implication
+* nat := PN : TYPE
+* zero := PN : nat
+* a := EB : PROP
+a * b := EB : PROP
+b * imp := [x:a]b : PROP
+b * hyp := EB : imp
+hyp * arg := EB : a
+arg * mp := <arg>hyp : b
+{ the identity, with an omitted argument }
+a * refl := [x:a]x : imp(a)
+a * selfimp := imp(a, a) : PROP;
+0 * pn := PN : [p:PROP][q:PROP]PROP
+@ * twice := [p:PROP]<p><p>pn : [p:PROP]PROP
+";
+
+    #[test]
+    fn implication() {
+        let book = Book::from_automath(IMPLICATION).unwrap();
+        let expected: Book = "\
+            def2\n0\nnat\n#\n*\nedef2\n\
+            def2\n0\nzero\n#\nnat[]\nedef2\n\
+            def2\n2\na\n*\nb\n*\nimp\n?x:(a).(b)\n*\nedef2\n\
+            def2\n4\na\n*\nb\n*\nh\nimp[(a),(b)]\nc\na\nmp\n%(h)(c)\nb\nedef2\n\
+            def2\n1\na\n*\nrefl\n$x:(a).(x)\nimp[(a),(a)]\nedef2\n\
+            def2\n1\na\n*\nselfimp\nimp[(a),(a)]\n*\nedef2\n\
+            def2\n0\npn\n#\n?p:(*).(?q:(*).(*))\nedef2\n\
+            def2\n0\ntwice\n$p:(*).(%(%(pn[])(p))(p))\n?p:(*).(*)\nedef2\nEND"
+            .parse()
+            .unwrap();
+        assert_eq!(book, expected);
+        book.check().unwrap();
+    }
+
+    #[rstest]
+    #[case("* a := EB : TYPE\na * b := PN : c", "line 2: unknown identifier c")]
+    #[case("x * a := PN : TYPE", "line 1: x is not a block variable")]
+    #[case("* f := PN : TYPE\n* g := f(f) : TYPE", "f takes at most 0 arguments")]
+    #[case(
+        "* a := EB : TYPE\na * f := PN : TYPE\n* g := f : TYPE",
+        "does not contain the parameter a of f"
+    )]
+    #[case(
+        "* x := EB : TYPE\nx * f := PN : TYPE\n* xx := EB : TYPE\nxx * g := f : TYPE",
+        "line 4: the context does not contain the parameter x of f"
+    )]
+    #[case(
+        "* a := EB : TYPE\na * f := PN : TYPE\n* g := [a:TYPE]f : TYPE",
+        "line 3: the context does not contain the parameter a of f"
+    )]
+    #[case("* a := [x:TYPE : TYPE", "line 1: expected ]")]
+    #[case("* a := PN", "unexpected end of input")]
+    #[case("* a := PN : $", "unexpected character '$'")]
+    fn errors(#[case] text: &str, #[case] message: &str) {
+        let error = Book::from_automath(text).unwrap_err();
+        assert!(error.contains(message), "{error}");
+    }
+
+    #[test]
+    fn renamed_names_round_trip() {
+        let book = Book::from_automath(
+            "* f := PN : TYPE\n* f_1 := f : TYPE\n* g' := f : TYPE\n* 1st := g' : TYPE",
+        )
+        .unwrap();
+        let names = book.0.iter().map(|b| b.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["f_2", "f_1", "g_", "d1st"]);
+        assert_eq!(book.to_string().parse::<Book>().unwrap(), book);
+        book.check().unwrap();
+
+        let book = Book::from_automath(IMPLICATION).unwrap();
+        assert_eq!(book.to_string().parse::<Book>().unwrap(), book);
+    }
+}
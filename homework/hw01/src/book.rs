@@ -0,0 +1,371 @@
+//! def2 books.
+//!
+//! A book is a sequence of blocks, each of the form
+//!
+//! ```text
+//! def2
+//! <number of parameters>
+//! <parameter>
+//! <parameter type>
+//! ...
+//! <name>
+//! <body, or # for a primitive definition>
+//! <type>
+//! edef2
+//! ```
+//!
+//! optionally followed by a final `END` line.
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    model::{Expr, Var},
+    parse::take_expr,
+    rule::Resolver,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Book(pub Vec<Block>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub context: Vec<(Var, Expr)>,
+    pub name: String,
+    /// `None` for primitive definitions, introduced by `defpr`.
+    pub m: Option<Expr>,
+    pub n: Expr,
+}
+
+impl Display for Block {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "def2")?;
+        writeln!(f, "{}", self.context.len())?;
+        for (x, a) in &self.context {
+            writeln!(f, "{}", x.0)?;
+            writeln!(f, "{}", a.plain())?;
+        }
+        writeln!(f, "{}", self.name)?;
+        match &self.m {
+            Some(m) => writeln!(f, "{}", m.plain())?,
+            None => writeln!(f, "#")?,
+        }
+        writeln!(f, "{}", self.n.plain())?;
+        writeln!(f, "edef2")
+    }
+}
+
+impl Display for Book {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for block in &self.0 {
+            writeln!(f, "{block}")?;
+        }
+        writeln!(f, "END")
+    }
+}
+
+struct Lines<'a, I: Iterator<Item = (usize, &'a str)>>(I);
+
+impl<'a, I: Iterator<Item = (usize, &'a str)>> Lines<'a, I> {
+    fn next(&mut self, what: &str) -> Result<(usize, &'a str)> {
+        self.0
+            .next()
+            .ok_or_else(|| format!("unexpected end of input, expecting {what}"))
+    }
+
+    fn exact(&mut self, expected: &str) -> Result<()> {
+        let (lineno, line) = self.next(expected)?;
+        if line == expected {
+            Ok(())
+        } else {
+            Err(format!(
+                "line {lineno}: expected {expected}, found {line:?}"
+            ))
+        }
+    }
+
+    fn expr(&mut self, what: &str) -> Result<Expr> {
+        let (lineno, line) = self.next(what)?;
+        parse_expr(line).map_err(|e| format!("line {lineno}: {what}: {e}"))
+    }
+
+    fn block(&mut self) -> Result<Block> {
+        let (lineno, line) = self.next("number of parameters")?;
+        let n = line
+            .parse::<usize>()
+            .map_err(|_| format!("line {lineno}: expected number of parameters"))?;
+
+        let mut context = Vec::with_capacity(n);
+        for _ in 0..n {
+            let (lineno, line) = self.next("parameter")?;
+            let x = line
+                .parse::<Var>()
+                .map_err(|e| format!("line {lineno}: parameter: {e}"))?;
+            context.push((x, self.expr("parameter type")?));
+        }
+
+        let (_, name) = self.next("definition name")?;
+        let m = match self.next("body")? {
+            (_, "#") => None,
+            (lineno, line) => {
+                Some(parse_expr(line).map_err(|e| format!("line {lineno}: body: {e}"))?)
+            }
+        };
+        let n = self.expr("type")?;
+        self.exact("edef2")?;
+
+        Ok(Block {
+            context,
+            name: name.to_string(),
+            m,
+            n,
+        })
+    }
+}
+
+pub(crate) fn parse_expr(line: &str) -> Result<Expr> {
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut input = chars.as_slice();
+    let e = take_expr(&mut input)?;
+    if input.is_empty() {
+        Ok(e)
+    } else {
+        Err(format!(
+            "unexpected trailing input {:?}",
+            input.iter().collect::<String>()
+        ))
+    }
+}
+
+impl FromStr for Book {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = Lines(
+            s.lines()
+                .enumerate()
+                .map(|(i, l)| (i + 1, l.trim()))
+                .filter(|(_, l)| !l.is_empty()),
+        );
+
+        let mut blocks = vec![];
+        while let Some((lineno, line)) = lines.0.next() {
+            match line {
+                "END" => break,
+                "def2" => blocks.push(lines.block()?),
+                line => {
+                    return Err(format!(
+                        "line {lineno}: expected def2 or END, found {line:?}"
+                    ));
+                }
+            }
+        }
+
+        Ok(Self(blocks))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookError {
+    pub block: usize,
+    pub name: String,
+    pub message: String,
+}
+
+impl Display for BookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "block {} ({}): {}", self.block, self.name, self.message)
+    }
+}
+
+impl Book {
+    pub fn get(&self, name: &str) -> Option<&Block> {
+        self.0.iter().find(|b| b.name == name)
+    }
+
+    /// Checks every block of the book, returning the resolver holding the
+    /// derivations and a judgement `* : @` in the environment of all
+    /// definitions.
+    pub fn check(&self) -> std::result::Result<(Resolver, usize), BookError> {
+        let mut resolver = Resolver::new();
+        let sort = resolver.sort();
+        let anchor = resolver.load(sort, self)?;
+        Ok((resolver, anchor))
+    }
+}
+
+impl Resolver {
+    /// Checks the blocks of `book` one by one and adds them to the
+    /// environment of `anchor`, a judgement `* : @` in the empty context.
+    /// Returns the corresponding judgement in the extended environment.
+    pub fn load(&mut self, anchor: usize, book: &Book) -> std::result::Result<usize, BookError> {
+        let mut anchor = anchor;
+        for (i, block) in book.0.iter().enumerate() {
+            anchor = self
+                .load_block(anchor, block)
+                .map_err(|message| BookError {
+                    block: i,
+                    name: block.name.clone(),
+                    message,
+                })?;
+        }
+        Ok(anchor)
+    }
+
+    fn load_block(&mut self, anchor: usize, block: &Block) -> Result<usize> {
+        let definitions = self.judgements[anchor].definitions;
+        if self
+            .definitions
+            .resolve(definitions, |d| d.name == block.name)
+            .is_some()
+        {
+            return Err(format!("{} is already defined", block.name));
+        }
+
+        let inner = self.start(anchor, &block.context)?;
+
+        let Some(m) = &block.m else {
+            let n = self.derive_type(inner, &block.n)?;
+            return Ok(self.def_prim(anchor, n, block.name.clone()));
+        };
+
+        let m = self.derive(inner, m)?;
+        let m = if block.n == Expr::Square {
+            let ty = &self.judgements[m].n;
+            if *ty != Expr::Square {
+                return Err(format!("the body has type {ty}, expected {}", Expr::Square));
+            }
+            m
+        } else {
+            self.convert(inner, m, &block.n)?
+        };
+
+        Ok(self.def(anchor, m, block.name.clone()))
+    }
+
+    /// The book of all definitions on the path `definitions`, in the order
+    /// they were introduced.
+    pub fn book(&self, definitions: usize) -> Book {
+        Book(
+            self.definitions
+                .path(definitions)
+                .into_iter()
+                .map(|d| Block {
+                    context: self.declarations(d.context),
+                    name: d.name.clone(),
+                    m: d.m.clone(),
+                    n: d.n.clone(),
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::verifier::Verifier;
+
+    use super::*;
+
+    #[test]
+    fn parse_def2() {
+        let book: Book = include_str!("../check/def2").parse().unwrap();
+        assert_eq!(book.0.len(), 14);
+
+        let implies_el = book.get("implies_el").unwrap();
+        assert_eq!(implies_el.context.len(), 4);
+        assert_eq!(implies_el.m, Some("%(u)(v)".parse().unwrap()));
+        assert_eq!(implies_el.n, "B".parse().unwrap());
+
+        assert_eq!(book.to_string().parse::<Book>().unwrap(), book);
+    }
+
+    #[test]
+    fn primitive_definitions() {
+        let book: Book = "def2\n1\nA\n*\nbot\n#\nA\nedef2\nEND\n".parse().unwrap();
+        assert_eq!(book.0[0].m, None);
+        assert!(book.to_string().contains("bot\n#\nA\n"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(
+            "def2\n1\nA\n*\nimplies\n?a:(A).(B)\n*\nEND"
+                .parse::<Book>()
+                .unwrap_err()
+                .contains("expected edef2")
+        );
+        assert!(
+            "def2\n0\nc\n*)\n*\nedef2"
+                .parse::<Book>()
+                .unwrap_err()
+                .contains("trailing input")
+        );
+    }
+
+    #[test]
+    fn check_def2() {
+        let book: Book = include_str!("../check/def2").parse().unwrap();
+        let (resolver, anchor) = book.check().unwrap();
+
+        let exported = resolver.book(resolver.judgements[anchor].definitions);
+        assert_eq!(exported.0.len(), book.0.len());
+        for (a, b) in exported.0.iter().zip(&book.0) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.context, b.context);
+            assert!(a.m.as_ref().unwrap().alpha_eq(b.m.as_ref().unwrap()));
+            assert!(a.n.alpha_eq(&b.n));
+        }
+    }
+
+    #[rstest]
+    #[case(
+        "def2\n0\nbot\n#\n*\nedef2\ndef2\n0\nbot\n#\n*\nedef2",
+        1,
+        "already defined"
+    )]
+    #[case(
+        "def2\n1\nA\n*\nnot\nimplies[(A),(A)]\n*\nedef2",
+        0,
+        "unknown definition implies"
+    )]
+    #[case("def2\n1\nA\nB\nid\nA\n*\nedef2", 0, "is not declared")]
+    #[case("def2\n1\nA\n*\nid\n$x:(A).(x)\n?x:(A).(*)\nedef2", 0, "expected")]
+    #[case("def2\n1\nA\n*\nid\nA\nA\nedef2", 0, "has type")]
+    #[case("def2\n0\nprop\n*\n@\nedef2\ndef2\n0\nbad\n*\n*\nedef2", 1, "has type")]
+    fn check_errors(#[case] input: &str, #[case] block: usize, #[case] message: &str) {
+        let book: Book = input.parse().unwrap();
+        let error = book.check().unwrap_err();
+        assert_eq!(error.block, block);
+        assert!(error.message.contains(message), "{error}");
+    }
+
+    #[test]
+    fn export_log() {
+        let v = Verifier::run(include_str!("../check/log"));
+        let resolver = v.resolver();
+        let book = resolver.book(resolver.judgements.last().unwrap().definitions);
+
+        let names = book.0.iter().map(|b| b.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["implies", "and", "and_in"]);
+
+        let def2: Book = include_str!("../check/def2").parse().unwrap();
+        for exported in &book.0 {
+            let expected = def2.get(&exported.name).unwrap();
+            assert_eq!(exported.context, expected.context);
+            assert!(
+                exported
+                    .m
+                    .as_ref()
+                    .unwrap()
+                    .alpha_eq(expected.m.as_ref().unwrap())
+            );
+            assert!(exported.n.alpha_eq(&expected.n));
+        }
+
+        assert_eq!(book.to_string().parse::<Book>().unwrap(), book);
+    }
+}
@@ -0,0 +1,490 @@
+//! Typed Church encodings of numerals, booleans and pairs.
+//!
+//! The encodings are written λ2 style: types are quantified over `*`, so a
+//! numeral is `$A:(*).($f:(?x:(A).(A)).($x:(A).(f (f … x))))` of type
+//! [`nat_type`]. Every function here returns a closed expression that derives
+//! in the empty context. [`eval`] normalizes a program and the `decode_*`
+//! functions read its normal form back into Rust values.
+
+use crate::{
+    de_bruijn,
+    model::{Application, Expr, Lambda, Var},
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+const NAT: &str = "?A:(*).(?f:(?x:(A).(A)).(?x:(A).(A)))";
+const BOOL: &str = "?A:(*).(?x:(A).(?y:(A).(A)))";
+
+/// Parses `template` with `{N}` and `{B}` replaced by the numeral and
+/// boolean types.
+fn parse(template: &str) -> Expr {
+    template
+        .replace("{N}", NAT)
+        .replace("{B}", BOOL)
+        .parse()
+        .unwrap_or_else(|e| panic!("church template {template}: {e}"))
+}
+
+/// `%(…%(f)(a1)…)(an)`.
+pub fn apply(f: &Expr, args: &[Expr]) -> Expr {
+    args.iter()
+        .fold(f.clone(), |f, a| Application(f, a.clone()).into())
+}
+
+/// Beta normal form of `e`.
+pub fn eval(e: &Expr) -> Result<Expr> {
+    e.normalize()
+}
+
+/// `?A:(*).(?f:(?x:(A).(A)).(?x:(A).(A)))`.
+pub fn nat_type() -> Expr {
+    parse(NAT)
+}
+
+/// `?A:(*).(?x:(A).(?y:(A).(A)))`.
+pub fn bool_type() -> Expr {
+    parse(BOOL)
+}
+
+/// The type of pairs of `a` and `b`, `?C:(*).(?f:(?x:(a).(?y:(b).(C))).(C))`.
+pub fn pair_type(a: &Expr, b: &Expr) -> Result<Expr> {
+    parse("?C:(*).(?f:(?x:(A).(?y:(B).(C))).(C))")
+        .substitute(&[(Var('A'), a.clone()), (Var('B'), b.clone())])
+}
+
+/// The numeral `n`, applying `f` `n` times to `x`.
+pub fn numeral(n: u64) -> Expr {
+    let (a, f, x) = (Var('A'), Var('f'), Var('x'));
+    let body = (0..n).fold(Expr::Var(x), |e, _| Application(f.into(), e).into());
+    let inner = Lambda(x, a.into(), body).into();
+    let inner = Lambda(f, parse("?x:(A).(A)"), inner).into();
+    Lambda(a, Expr::Asterisk, inner).into()
+}
+
+/// The numerals `0` to `n`.
+pub fn numerals(n: u64) -> Vec<Expr> {
+    (0..=n).map(numeral).collect()
+}
+
+pub fn succ() -> Expr {
+    parse("$n:({N}).($A:(*).($f:(?x:(A).(A)).($x:(A).(%(f)(%(%(%(n)(A))(f))(x))))))")
+}
+
+pub fn plus() -> Expr {
+    parse(
+        "$m:({N}).($n:({N}).($A:(*).($f:(?x:(A).(A)).($x:(A).\
+         (%(%(%(m)(A))(f))(%(%(%(n)(A))(f))(x)))))))",
+    )
+}
+
+pub fn times() -> Expr {
+    parse("$m:({N}).($n:({N}).($A:(*).($f:(?x:(A).(A)).(%(%(m)(A))(%(%(n)(A))(f))))))")
+}
+
+/// `m` to the power of `n`, multiplying `1` by `m` `n` times.
+pub fn power() -> Expr {
+    let (m, n) = (Expr::Var(Var('m')), Expr::Var(Var('n')));
+    let body = apply(&n, &[nat_type(), apply(&times(), &[m]), numeral(1)]);
+    Lambda(
+        Var('m'),
+        nat_type(),
+        Lambda(Var('n'), nat_type(), body).into(),
+    )
+    .into()
+}
+
+/// The predecessor, with `pred 0 = 0`. Iterates `(a, b) ↦ (b, b + 1)` from
+/// `(0, 0)` and takes the first component.
+pub fn pred() -> Expr {
+    let (nat, p, n) = (nat_type(), Expr::Var(Var('p')), Expr::Var(Var('n')));
+    let pair_nat = pair_type(&nat, &nat).expect("nat_type is closed");
+    let second = apply(&snd(), &[nat.clone(), nat.clone(), p]);
+    let shifted = apply(
+        &pair(),
+        &[
+            nat.clone(),
+            nat.clone(),
+            second.clone(),
+            apply(&succ(), &[second]),
+        ],
+    );
+    let step = Lambda(Var('p'), pair_nat.clone(), shifted).into();
+    let start = apply(&pair(), &[nat.clone(), nat.clone(), numeral(0), numeral(0)]);
+    let iterated = apply(&n, &[pair_nat, step, start]);
+    let body = apply(&fst(), &[nat.clone(), nat.clone(), iterated]);
+    Lambda(Var('n'), nat, body).into()
+}
+
+/// Truncated subtraction, applying `pred` `n` times to `m`.
+pub fn minus() -> Expr {
+    let (m, n) = (Expr::Var(Var('m')), Expr::Var(Var('n')));
+    let body = apply(&n, &[nat_type(), pred(), m]);
+    Lambda(
+        Var('m'),
+        nat_type(),
+        Lambda(Var('n'), nat_type(), body).into(),
+    )
+    .into()
+}
+
+pub fn is_zero() -> Expr {
+    let n = Expr::Var(Var('n'));
+    let never = Lambda(Var('b'), bool_type(), boolean(false)).into();
+    let body = apply(&n, &[bool_type(), never, boolean(true)]);
+    Lambda(Var('n'), nat_type(), body).into()
+}
+
+/// `m ≤ n`, that is `m - n = 0`.
+pub fn leq() -> Expr {
+    let (m, n) = (Expr::Var(Var('m')), Expr::Var(Var('n')));
+    let body = apply(&is_zero(), &[apply(&minus(), &[m, n])]);
+    Lambda(
+        Var('m'),
+        nat_type(),
+        Lambda(Var('n'), nat_type(), body).into(),
+    )
+    .into()
+}
+
+/// `true` selects the first of two alternatives, `false` the second.
+pub fn boolean(b: bool) -> Expr {
+    parse(if b {
+        "$A:(*).($x:(A).($y:(A).(x)))"
+    } else {
+        "$A:(*).($x:(A).($y:(A).(y)))"
+    })
+}
+
+pub fn not() -> Expr {
+    parse("$b:({B}).($A:(*).($x:(A).($y:(A).(%(%(%(b)(A))(y))(x)))))")
+}
+
+pub fn and() -> Expr {
+    let (a, b) = (Expr::Var(Var('a')), Expr::Var(Var('b')));
+    let body = apply(&a, &[bool_type(), b, boolean(false)]);
+    Lambda(
+        Var('a'),
+        bool_type(),
+        Lambda(Var('b'), bool_type(), body).into(),
+    )
+    .into()
+}
+
+pub fn or() -> Expr {
+    let (a, b) = (Expr::Var(Var('a')), Expr::Var(Var('b')));
+    let body = apply(&a, &[bool_type(), boolean(true), b]);
+    Lambda(
+        Var('a'),
+        bool_type(),
+        Lambda(Var('b'), bool_type(), body).into(),
+    )
+    .into()
+}
+
+/// `if b then x else y` for `x` and `y` of type `A`, taking `A`, `b`, `x`
+/// and `y`.
+pub fn cond() -> Expr {
+    parse("$A:(*).($b:({B}).(%(b)(A)))")
+}
+
+/// The pair constructor, taking the component types and then the components.
+pub fn pair() -> Expr {
+    parse(
+        "$A:(*).($B:(*).($a:(A).($b:(B).\
+         ($C:(*).($f:(?x:(A).(?y:(B).(C))).(%(%(f)(a))(b)))))))",
+    )
+}
+
+/// The first projection, taking the component types and then the pair.
+pub fn fst() -> Expr {
+    projection(Var('a'))
+}
+
+/// The second projection, taking the component types and then the pair.
+pub fn snd() -> Expr {
+    projection(Var('b'))
+}
+
+fn projection(component: Var) -> Expr {
+    let (a, b) = (Expr::Var(Var('A')), Expr::Var(Var('B')));
+    let ty = if component == Var('a') { &a } else { &b };
+    let select = Lambda(
+        Var('a'),
+        a.clone(),
+        Lambda(Var('b'), b.clone(), component.into()).into(),
+    )
+    .into();
+    let body = apply(&Expr::Var(Var('p')), &[ty.clone(), select]);
+    let ty = pair_type(&a, &b).expect("A and B are not bound in the pair type");
+    let inner = Lambda(Var('p'), ty, body).into();
+    Lambda(
+        Var('A'),
+        Expr::Asterisk,
+        Lambda(Var('B'), Expr::Asterisk, inner).into(),
+    )
+    .into()
+}
+
+/// Strips `count` lambdas off `e`, returning their types and the body.
+fn lambdas(e: &de_bruijn::Expr, count: usize) -> Option<(Vec<&de_bruijn::Expr>, &de_bruijn::Expr)> {
+    let mut types = vec![];
+    let mut e = e;
+    for _ in 0..count {
+        let de_bruijn::Expr::Lambda(l) = e else {
+            return None;
+        };
+        types.push(&l.1);
+        e = &l.2;
+    }
+    Some((types, e))
+}
+
+fn bound(e: &de_bruijn::Expr) -> Option<usize> {
+    match e {
+        de_bruijn::Expr::Var(de_bruijn::Var::Bound(i, _)) => Some(*i),
+        _ => None,
+    }
+}
+
+/// Whether `e` is `A → A` for the type `A` bound just outside it.
+fn is_endo(e: &de_bruijn::Expr) -> bool {
+    matches!(e, de_bruijn::Expr::Pi(pi) if bound(&pi.1) == Some(1) && bound(&pi.2) == Some(2))
+}
+
+/// Whether `e` is `A → B → C` for closed `A` and `B` and the type `C` bound
+/// just outside it.
+fn is_pair_eliminator(e: &de_bruijn::Expr) -> bool {
+    let de_bruijn::Expr::Pi(x) = e else {
+        return false;
+    };
+    let de_bruijn::Expr::Pi(y) = &x.2 else {
+        return false;
+    };
+    !x.1.has_loose_indices() && !y.1.has_loose_indices() && bound(&y.2) == Some(3)
+}
+
+/// Reads a numeral back from its normal form.
+pub fn decode_nat(e: &Expr) -> Result<u64> {
+    let error = || format!("{} is not a Church numeral", e.plain());
+    let e = e.de_bruijn();
+    let (types, mut body) = lambdas(&e, 3).ok_or_else(error)?;
+    if *types[0] != de_bruijn::Expr::Asterisk || !is_endo(types[1]) || bound(types[2]) != Some(2) {
+        return Err(error());
+    }
+    let mut n = 0;
+    while let de_bruijn::Expr::Application(a) = body {
+        if bound(&a.0) != Some(2) {
+            return Err(error());
+        }
+        n += 1;
+        body = &a.1;
+    }
+    match bound(body) {
+        Some(1) => Ok(n),
+        _ => Err(error()),
+    }
+}
+
+/// Reads a boolean back from its normal form.
+pub fn decode_bool(e: &Expr) -> Result<bool> {
+    let error = || format!("{} is not a Church boolean", e.plain());
+    let e = e.de_bruijn();
+    let (types, body) = lambdas(&e, 3).ok_or_else(error)?;
+    if *types[0] != de_bruijn::Expr::Asterisk
+        || bound(types[1]) != Some(1)
+        || bound(types[2]) != Some(2)
+    {
+        return Err(error());
+    }
+    match bound(body) {
+        Some(2) => Ok(true),
+        Some(1) => Ok(false),
+        _ => Err(error()),
+    }
+}
+
+/// Reads the components back from the normal form of a pair.
+pub fn decode_pair(e: &Expr) -> Result<(Expr, Expr)> {
+    let error = || format!("{} is not a Church pair", e.plain());
+    let de_bruijn::Expr::Lambda(c) = e.de_bruijn() else {
+        return Err(error());
+    };
+    let de_bruijn::Expr::Lambda(f) = &c.2 else {
+        return Err(error());
+    };
+    let de_bruijn::Expr::Application(outer) = &f.2 else {
+        return Err(error());
+    };
+    let de_bruijn::Expr::Application(inner) = &outer.0 else {
+        return Err(error());
+    };
+    let (a, b) = (&inner.1, &outer.1);
+    if c.1 != de_bruijn::Expr::Asterisk
+        || !is_pair_eliminator(&f.1)
+        || bound(&inner.0) != Some(1)
+        || a.has_loose_indices()
+        || b.has_loose_indices()
+    {
+        return Err(error());
+    }
+    Ok((a.clone().try_into()?, b.clone().try_into()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::rule::Resolver;
+
+    fn run_nat(f: Expr, args: &[u64]) -> u64 {
+        let args = args.iter().map(|&n| numeral(n)).collect::<Vec<_>>();
+        decode_nat(&eval(&apply(&f, &args)).unwrap()).unwrap()
+    }
+
+    fn run_bool(f: Expr, args: &[bool]) -> bool {
+        let args = args.iter().map(|&b| boolean(b)).collect::<Vec<_>>();
+        decode_bool(&eval(&apply(&f, &args)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn numerals_round_trip() {
+        for (n, e) in numerals(10).iter().enumerate() {
+            assert_eq!(decode_nat(e), Ok(n as u64));
+        }
+    }
+
+    #[rstest]
+    #[case(succ(), &[0], 1)]
+    #[case(succ(), &[4], 5)]
+    #[case(plus(), &[2, 3], 5)]
+    #[case(plus(), &[0, 0], 0)]
+    #[case(times(), &[3, 4], 12)]
+    #[case(times(), &[5, 0], 0)]
+    #[case(power(), &[2, 3], 8)]
+    #[case(power(), &[3, 0], 1)]
+    #[case(pred(), &[0], 0)]
+    #[case(pred(), &[6], 5)]
+    #[case(minus(), &[7, 3], 4)]
+    #[case(minus(), &[3, 7], 0)]
+    fn arithmetic(#[case] f: Expr, #[case] args: &[u64], #[case] expected: u64) {
+        assert_eq!(run_nat(f, args), expected);
+    }
+
+    #[rstest]
+    #[case(0, true)]
+    #[case(3, false)]
+    fn zero_test(#[case] n: u64, #[case] expected: bool) {
+        let e = eval(&apply(&is_zero(), &[numeral(n)])).unwrap();
+        assert_eq!(decode_bool(&e), Ok(expected));
+    }
+
+    #[rstest]
+    #[case(2, 3, true)]
+    #[case(3, 3, true)]
+    #[case(4, 3, false)]
+    fn comparison(#[case] m: u64, #[case] n: u64, #[case] expected: bool) {
+        let e = eval(&apply(&leq(), &[numeral(m), numeral(n)])).unwrap();
+        assert_eq!(decode_bool(&e), Ok(expected));
+    }
+
+    #[rstest]
+    #[case(not(), &[true], false)]
+    #[case(not(), &[false], true)]
+    #[case(and(), &[true, true], true)]
+    #[case(and(), &[true, false], false)]
+    #[case(or(), &[false, true], true)]
+    #[case(or(), &[false, false], false)]
+    fn logic(#[case] f: Expr, #[case] args: &[bool], #[case] expected: bool) {
+        assert_eq!(run_bool(f, args), expected);
+    }
+
+    #[rstest]
+    #[case(true, 1)]
+    #[case(false, 2)]
+    fn conditional(#[case] b: bool, #[case] expected: u64) {
+        let e = apply(&cond(), &[nat_type(), boolean(b), numeral(1), numeral(2)]);
+        assert_eq!(decode_nat(&eval(&e).unwrap()), Ok(expected));
+    }
+
+    #[test]
+    fn pairs() {
+        let p = apply(
+            &pair(),
+            &[nat_type(), bool_type(), numeral(3), boolean(true)],
+        );
+        let (a, b) = decode_pair(&eval(&p).unwrap()).unwrap();
+        assert_eq!(decode_nat(&a), Ok(3));
+        assert_eq!(decode_bool(&b), Ok(true));
+
+        let first = apply(&fst(), &[nat_type(), bool_type(), p.clone()]);
+        let second = apply(&snd(), &[nat_type(), bool_type(), p]);
+        assert_eq!(decode_nat(&eval(&first).unwrap()), Ok(3));
+        assert_eq!(decode_bool(&eval(&second).unwrap()), Ok(true));
+    }
+
+    #[rstest]
+    #[case("$A:(*).($f:(?x:(A).(A)).(f))", "is not a Church numeral")]
+    #[case(
+        "$A:(*).($f:(?x:(A).(A)).($x:(A).(%(x)(f))))",
+        "is not a Church numeral"
+    )]
+    fn not_a_numeral(#[case] e: Expr, #[case] expected: &str) {
+        assert!(decode_nat(&e).unwrap_err().contains(expected));
+    }
+
+    #[test]
+    fn mismatched_decoders() {
+        assert!(decode_bool(&numeral(2)).is_err());
+        assert!(decode_pair(&boolean(true)).is_err());
+        assert!(decode_bool(&numeral(0)).is_err());
+        assert!(decode_nat(&boolean(false)).is_err());
+        let e = "$C:(*).($f:(C).(%(%(f)(a))(b)))".parse().unwrap();
+        assert!(decode_pair(&e).is_err());
+        let e = "$C:(*).($f:(?x:(A).(?y:(B).(x))).(%(%(f)(a))(b)))"
+            .parse()
+            .unwrap();
+        assert!(decode_pair(&e).is_err());
+    }
+
+    #[rstest]
+    #[case(numeral(3), "{N}")]
+    #[case(boolean(true), "{B}")]
+    #[case(succ(), "?n:({N}).({N})")]
+    #[case(plus(), "?m:({N}).(?n:({N}).({N}))")]
+    #[case(times(), "?m:({N}).(?n:({N}).({N}))")]
+    #[case(power(), "?m:({N}).(?n:({N}).({N}))")]
+    #[case(pred(), "?n:({N}).({N})")]
+    #[case(minus(), "?m:({N}).(?n:({N}).({N}))")]
+    #[case(is_zero(), "?n:({N}).({B})")]
+    #[case(leq(), "?m:({N}).(?n:({N}).({B}))")]
+    #[case(not(), "?b:({B}).({B})")]
+    #[case(and(), "?a:({B}).(?b:({B}).({B}))")]
+    #[case(or(), "?a:({B}).(?b:({B}).({B}))")]
+    #[case(cond(), "?A:(*).(?b:({B}).(?x:(A).(?y:(A).(A))))")]
+    #[case(
+        pair(),
+        "?A:(*).(?B:(*).(?a:(A).(?b:(B).(?C:(*).(?f:(?x:(A).(?y:(B).(C))).(C))))))"
+    )]
+    #[case(
+        fst(),
+        "?A:(*).(?B:(*).(?p:(?C:(*).(?f:(?x:(A).(?y:(B).(C))).(C))).(A)))"
+    )]
+    #[case(
+        snd(),
+        "?A:(*).(?B:(*).(?p:(?C:(*).(?f:(?x:(A).(?y:(B).(C))).(C))).(B)))"
+    )]
+    fn typed(#[case] e: Expr, #[case] expected: &str) {
+        let mut resolver = Resolver::new();
+        let anchor = resolver.sort();
+        let j = resolver.derive(anchor, &e).unwrap();
+        let judgement = &resolver.judgements[j];
+        assert!(
+            resolver.convertible(judgement.definitions, &judgement.n, &parse(expected)),
+            "{} : {}",
+            e.plain(),
+            judgement.n.plain()
+        );
+    }
+}
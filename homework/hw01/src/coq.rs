@@ -0,0 +1,197 @@
+//! Export of books to Coq (Rocq) vernacular.
+//!
+//! `*` becomes `Prop` and `@` becomes `Type`. Every block becomes a
+//! `Definition` with its parameters as binders, or an `Axiom` quantifying
+//! over them; instances of definitions become applications to all
+//! arguments. The output is wrapped in a module.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    book::Book,
+    export::{Identifiers, Level, Syntax},
+    model::Expr,
+};
+
+const KEYWORDS: &[&str] = &[
+    "as",
+    "at",
+    "Axiom",
+    "cofix",
+    "Definition",
+    "else",
+    "End",
+    "end",
+    "exists",
+    "exists2",
+    "fix",
+    "for",
+    "forall",
+    "fun",
+    "if",
+    "IF",
+    "in",
+    "Inductive",
+    "Lemma",
+    "let",
+    "match",
+    "mod",
+    "Module",
+    "Parameter",
+    "Prop",
+    "Proof",
+    "Qed",
+    "return",
+    "Section",
+    "Set",
+    "SProp",
+    "then",
+    "Theorem",
+    "Type",
+    "using",
+    "Variable",
+    "where",
+    "with",
+];
+
+/// Invalid characters in names are replaced with `_`, and keywords are
+/// suffixed with `_`.
+const COQ: Syntax = Syntax {
+    identifiers: Identifiers {
+        keywords: KEYWORDS,
+        escape: |name| {
+            let mut s = name
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || c == '_' || c == '\'' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect::<String>();
+            if !s.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                s.insert(0, 'x');
+            }
+            if KEYWORDS.contains(&s.as_str()) {
+                s.push('_');
+            }
+            s
+        },
+    },
+    asterisk: "Prop",
+    square: "Type",
+    arrow: " -> ",
+    pi: |x, a, b| format!("forall {x} : {a}, {b}"),
+    lambda: |x, a, b| format!("fun {x} : {a} => {b}"),
+    domain: Level::Application,
+};
+
+/// Sanitized names of the definitions of a book, made unique.
+struct Names(HashMap<String, String>);
+
+impl Names {
+    fn new(book: &Book) -> Self {
+        let mut names = HashMap::new();
+        let mut taken = KEYWORDS.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+        for block in &book.0 {
+            let mut name = COQ.sanitize(&block.name);
+            while taken.contains(&name) {
+                name.push('\'');
+            }
+            taken.push(name.clone());
+            names.insert(block.name.clone(), name);
+        }
+        Self(names)
+    }
+
+    fn get(&self, name: &str) -> String {
+        self.0
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| COQ.sanitize(name))
+    }
+
+    fn expr(&self, e: &Expr, level: Level) -> String {
+        COQ.expr(e, level, &|name| self.get(name))
+    }
+}
+
+impl Book {
+    /// The book as a Coq source file, with all definitions in the module
+    /// `module`.
+    pub fn to_coq(&self, module: &str) -> String {
+        let names = Names::new(self);
+        let module = COQ.sanitize(module);
+
+        let mut out = String::new();
+        writeln!(out, "Module {module}.\n").unwrap();
+        for block in &self.0 {
+            let binders = block
+                .context
+                .iter()
+                .map(|(x, a)| format!("({} : {})", x.0, names.expr(a, Level::Binder)))
+                .collect::<Vec<_>>();
+            let name = names.get(&block.name);
+            let n = names.expr(&block.n, Level::Binder);
+            match &block.m {
+                Some(m) => {
+                    write!(out, "Definition {name}").unwrap();
+                    for binder in &binders {
+                        write!(out, " {binder}").unwrap();
+                    }
+                    writeln!(out, " : {n} :=\n  {}.\n", names.expr(m, Level::Binder)).unwrap();
+                }
+                None if binders.is_empty() => writeln!(out, "Axiom {name} : {n}.\n").unwrap(),
+                None => {
+                    writeln!(out, "Axiom {name} : forall {}, {n}.\n", binders.join(" ")).unwrap()
+                }
+            }
+        }
+        writeln!(out, "End {module}.").unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("fun", "fun_")]
+    #[case("1st", "x1st")]
+    #[case("a-b", "a_b")]
+    fn sanitize_names(#[case] name: &str, #[case] expected: &str) {
+        assert_eq!(COQ.sanitize(name), expected);
+    }
+
+    #[rstest]
+    #[case("?x:(*).(x)", "forall x : Prop, x")]
+    #[case("?x:(A).(B)", "A -> B")]
+    #[case("?x:(?y:(*).(y)).(x)", "forall x : (forall y : Prop, y), x")]
+    #[case("$x:(A).(%(f)(x))", "fun x : A => f x")]
+    #[case("?x:(*).(@)", "Prop -> Type")]
+    fn expressions(#[case] e: Expr, #[case] expected: &str) {
+        let names = Names::new(&Book::default());
+        assert_eq!(names.expr(&e, Level::Binder), expected);
+    }
+
+    #[test]
+    fn export_axioms_and_clashes() {
+        let book: Book = "def2\n0\nfun\n#\n*\nedef2\n\
+                          def2\n1\nx\nfun[]\nfun_\n#\n*\nedef2\n\
+                          def2\n0\nfun-\nfun_[]\n*\nedef2\nEND"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            book.to_coq("T"),
+            "Module T.\n\n\
+             Axiom fun_ : Prop.\n\n\
+             Axiom fun_' : forall (x : fun_), Prop.\n\n\
+             Definition fun_'' : Prop :=\n  fun_'.\n\n\
+             End T.\n"
+        );
+    }
+}
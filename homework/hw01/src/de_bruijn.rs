@@ -1,8 +1,13 @@
-use std::collections::HashSet;
+use std::{
+    collections::HashSet,
+    hash::{Hash, Hasher},
+};
 
 use crate::model::generate_free_var_gte;
 
-#[derive(Clone, Debug, PartialEq)]
+/// Equality and hashing ignore binder names, so an `Expr` is a canonical key
+/// for its alpha-equivalence class.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Expr {
     Var(Var),
     Asterisk,
@@ -11,21 +16,17 @@ pub enum Expr {
     Pi(Box<Pi>),
     Definition(Definition),
     Application(Box<Application>),
+    Meta(usize),
+    Hole(Option<String>),
 }
 
-impl From<Expr> for crate::model::Expr {
-    fn from(value: Expr) -> Self {
-        match value {
-            Expr::Var(Var::Bound(_, v) | Var::Free(v)) => crate::model::Expr::Var(v),
-            Expr::Asterisk => crate::model::Expr::Asterisk,
-            Expr::Square => crate::model::Expr::Square,
-            Expr::Lambda(l) => crate::model::Lambda(l.0, l.1.into(), l.2.into()).into(),
-            Expr::Pi(pi) => crate::model::Pi(pi.0, pi.1.into(), pi.2.into()).into(),
-            Expr::Definition(d) => {
-                crate::model::Definition(d.0, d.1.into_iter().map(Into::into).collect()).into()
-            }
-            Expr::Application(a) => crate::model::Application(a.0.into(), a.1.into()).into(),
-        }
+/// Fails when a binder cannot be named without capturing a variable, as a
+/// named expression has only 52 variable names.
+impl TryFrom<Expr> for crate::model::Expr {
+    type Error = String;
+
+    fn try_from(value: Expr) -> Result<Self, String> {
+        value.named(&mut vec![])
     }
 }
 
@@ -45,6 +46,18 @@ impl PartialEq for Var {
     }
 }
 
+impl Eq for Var {}
+
+impl Hash for Var {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::Free(v) => v.hash(state),
+            Self::Bound(i, _) => i.hash(state),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Lambda(pub crate::model::Var, pub Expr, pub Expr);
 
@@ -54,6 +67,15 @@ impl PartialEq for Lambda {
     }
 }
 
+impl Eq for Lambda {}
+
+impl Hash for Lambda {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.1.hash(state);
+        self.2.hash(state);
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Pi(pub crate::model::Var, pub Expr, pub Expr);
 
@@ -63,22 +85,278 @@ impl PartialEq for Pi {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+impl Eq for Pi {}
+
+impl Hash for Pi {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.1.hash(state);
+        self.2.hash(state);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Definition(pub String, pub Vec<Expr>);
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Application(pub Expr, pub Expr);
 
-#[derive(Clone, Debug)]
-pub struct Bindings {
-    stack: Vec<Binding>,
-    substitution: Option<(crate::model::Var, crate::model::Expr)>,
+impl Expr {
+    /// Converts back to a named expression. Binders keep their recorded name
+    /// unless it would capture a free variable or an outer binder referenced
+    /// in the body, in which case the next free name is used instead.
+    fn named(&self, scope: &mut Vec<crate::model::Var>) -> Result<crate::model::Expr, String> {
+        Ok(match self {
+            Expr::Var(Var::Bound(i, v)) => {
+                crate::model::Expr::Var(scope.len().checked_sub(*i).map_or(*v, |k| scope[k]))
+            }
+            Expr::Var(Var::Free(v)) => crate::model::Expr::Var(*v),
+            Expr::Asterisk => crate::model::Expr::Asterisk,
+            Expr::Square => crate::model::Expr::Square,
+            Expr::Meta(m) => crate::model::Expr::Meta(*m),
+            Expr::Hole(h) => crate::model::Expr::Hole(h.clone()),
+            Expr::Lambda(l) => {
+                let (x, a, b) = Self::named_binder(l.0, &l.1, &l.2, scope)?;
+                crate::model::Lambda(x, a, b).into()
+            }
+            Expr::Pi(pi) => {
+                let (x, a, b) = Self::named_binder(pi.0, &pi.1, &pi.2, scope)?;
+                crate::model::Pi(x, a, b).into()
+            }
+            Expr::Definition(d) => crate::model::Definition(
+                d.0.clone(),
+                d.1.iter()
+                    .map(|e| e.named(scope))
+                    .collect::<Result<_, _>>()?,
+            )
+            .into(),
+            Expr::Application(a) => {
+                crate::model::Application(a.0.named(scope)?, a.1.named(scope)?).into()
+            }
+        })
+    }
+
+    fn named_binder(
+        var: crate::model::Var,
+        ty: &Expr,
+        body: &Expr,
+        scope: &mut Vec<crate::model::Var>,
+    ) -> Result<(crate::model::Var, crate::model::Expr, crate::model::Expr), String> {
+        let ty = ty.named(scope)?;
+        let mut avoid = body.free_vars();
+        let mut loose = HashSet::new();
+        body.loose_indices(1, &mut loose);
+        avoid.extend(
+            loose
+                .into_iter()
+                .filter_map(|i| scope.len().checked_sub(i).map(|k| scope[k])),
+        );
+        let var = generate_free_var_gte(&avoid, var)?;
+        scope.push(var);
+        let body = body.named(scope);
+        scope.pop();
+        Ok((var, ty, body?))
+    }
+
+    /// Collects bound indices that point outside of `depth` enclosing
+    /// binders, relative to the innermost binder outside of them.
+    fn loose_indices(&self, depth: usize, out: &mut HashSet<usize>) {
+        match self {
+            Expr::Var(Var::Bound(i, _)) if *i > depth => {
+                out.insert(*i - depth);
+            }
+            Expr::Var(_) | Expr::Asterisk | Expr::Square | Expr::Meta(_) | Expr::Hole(_) => {}
+            Expr::Lambda(l) => {
+                l.1.loose_indices(depth, out);
+                l.2.loose_indices(depth + 1, out);
+            }
+            Expr::Pi(pi) => {
+                pi.1.loose_indices(depth, out);
+                pi.2.loose_indices(depth + 1, out);
+            }
+            Expr::Definition(d) => d.1.iter().for_each(|e| e.loose_indices(depth, out)),
+            Expr::Application(a) => {
+                a.0.loose_indices(depth, out);
+                a.1.loose_indices(depth, out);
+            }
+        }
+    }
+
+    /// Whether some bound index points outside of the expression.
+    pub fn has_loose_indices(&self) -> bool {
+        let mut loose = HashSet::new();
+        self.loose_indices(0, &mut loose);
+        !loose.is_empty()
+    }
+
+    pub fn free_vars(&self) -> HashSet<crate::model::Var> {
+        match self {
+            Expr::Var(Var::Free(v)) => HashSet::from([*v]),
+            Expr::Var(Var::Bound(..))
+            | Expr::Asterisk
+            | Expr::Square
+            | Expr::Meta(_)
+            | Expr::Hole(_) => HashSet::new(),
+            Expr::Lambda(l) => {
+                let mut fv = l.1.free_vars();
+                fv.extend(l.2.free_vars());
+                fv
+            }
+            Expr::Pi(pi) => {
+                let mut fv = pi.1.free_vars();
+                fv.extend(pi.2.free_vars());
+                fv
+            }
+            Expr::Definition(d) => d.1.iter().fold(HashSet::new(), |mut fv, e| {
+                fv.extend(e.free_vars());
+                fv
+            }),
+            Expr::Application(a) => {
+                let mut fv = a.0.free_vars();
+                fv.extend(a.1.free_vars());
+                fv
+            }
+        }
+    }
+
+    /// Rebuilds the expression, replacing every variable with `f(var, depth)`
+    /// where `depth` is the number of binders crossed so far.
+    fn map_vars(&self, depth: usize, f: &impl Fn(&Var, usize) -> Expr) -> Expr {
+        match self {
+            Expr::Var(v) => f(v, depth),
+            Expr::Asterisk | Expr::Square | Expr::Meta(_) | Expr::Hole(_) => self.clone(),
+            Expr::Lambda(l) => Expr::Lambda(Box::new(Lambda(
+                l.0,
+                l.1.map_vars(depth, f),
+                l.2.map_vars(depth + 1, f),
+            ))),
+            Expr::Pi(pi) => Expr::Pi(Box::new(Pi(
+                pi.0,
+                pi.1.map_vars(depth, f),
+                pi.2.map_vars(depth + 1, f),
+            ))),
+            Expr::Definition(d) => Expr::Definition(Definition(
+                d.0.clone(),
+                d.1.iter().map(|e| e.map_vars(depth, f)).collect(),
+            )),
+            Expr::Application(a) => Expr::Application(Box::new(Application(
+                a.0.map_vars(depth, f),
+                a.1.map_vars(depth, f),
+            ))),
+        }
+    }
+
+    /// Shifts every bound index above `cutoff` up by `by`.
+    pub fn lift(&self, by: usize, cutoff: usize) -> Expr {
+        if by == 0 {
+            return self.clone();
+        }
+        self.map_vars(0, &|v, depth| match v {
+            Var::Bound(i, x) if *i > cutoff + depth => Expr::Var(Var::Bound(i + by, *x)),
+            v => Expr::Var(v.clone()),
+        })
+    }
+
+    /// Substitutes `value` for index 1, i.e. instantiates the body of the
+    /// binder this expression was taken from.
+    pub fn instantiate(&self, value: &Expr) -> Expr {
+        self.map_vars(0, &|v, depth| match v {
+            Var::Bound(i, _) if *i == depth + 1 => value.lift(depth, 0),
+            Var::Bound(i, x) if *i > depth + 1 => Expr::Var(Var::Bound(i - 1, *x)),
+            v => Expr::Var(v.clone()),
+        })
+    }
+
+    /// Instantiates the outermost binder with the free variable `var`.
+    pub fn open(&self, var: crate::model::Var) -> Expr {
+        self.instantiate(&Expr::Var(Var::Free(var)))
+    }
+
+    /// Turns the free variable `var` into index 1, the inverse of [`Expr::open`].
+    pub fn close(&self, var: crate::model::Var) -> Expr {
+        self.map_vars(0, &|v, depth| match v {
+            Var::Free(x) if *x == var => Expr::Var(Var::Bound(depth + 1, *x)),
+            Var::Bound(i, x) if *i > depth => Expr::Var(Var::Bound(i + 1, *x)),
+            v => Expr::Var(v.clone()),
+        })
+    }
+
+    /// Simultaneously substitutes free variables.
+    pub fn substitute(&self, substitution: &[(crate::model::Var, Expr)]) -> Expr {
+        self.map_vars(0, &|v, depth| match v {
+            Var::Free(x) => substitution
+                .iter()
+                .find(|(y, _)| x == y)
+                .map_or_else(|| Expr::Var(v.clone()), |(_, e)| e.lift(depth, 0)),
+            v => Expr::Var(v.clone()),
+        })
+    }
+
+    fn head_step(&self, delta: &impl Fn(&Definition) -> Option<Expr>) -> Option<Expr> {
+        match self {
+            Expr::Definition(d) => delta(d),
+            Expr::Application(a) => match &a.0 {
+                Expr::Lambda(l) => Some(l.2.instantiate(&a.1)),
+                f => f
+                    .head_step(delta)
+                    .map(|f| Expr::Application(Box::new(Application(f, a.1.clone())))),
+            },
+            _ => None,
+        }
+    }
+
+    /// Beta-reduces the head of the expression until it is no longer a redex.
+    pub fn whnf(&self) -> Expr {
+        self.whnf_with(&|_| None)
+    }
+
+    /// Like [`Expr::whnf`], additionally unfolding definitions in head
+    /// position whenever `delta` returns their body.
+    pub fn whnf_with(&self, delta: &impl Fn(&Definition) -> Option<Expr>) -> Expr {
+        let mut e = self.clone();
+        while let Some(next) = e.head_step(delta) {
+            e = next;
+        }
+        e
+    }
+
+    /// Beta normal form. Only terminates for normalizing terms, which includes
+    /// every well-typed term.
+    pub fn normalize(&self) -> Expr {
+        self.normalize_with(&|_| None)
+    }
+
+    /// Beta-delta normal form, unfolding definitions through `delta`.
+    pub fn normalize_with(&self, delta: &impl Fn(&Definition) -> Option<Expr>) -> Expr {
+        match self.whnf_with(delta) {
+            Expr::Lambda(l) => Expr::Lambda(Box::new(Lambda(
+                l.0,
+                l.1.normalize_with(delta),
+                l.2.normalize_with(delta),
+            ))),
+            Expr::Pi(pi) => Expr::Pi(Box::new(Pi(
+                pi.0,
+                pi.1.normalize_with(delta),
+                pi.2.normalize_with(delta),
+            ))),
+            Expr::Definition(d) => Expr::Definition(Definition(
+                d.0,
+                d.1.iter().map(|e| e.normalize_with(delta)).collect(),
+            )),
+            Expr::Application(a) => Expr::Application(Box::new(Application(
+                a.0.normalize_with(delta),
+                a.1.normalize_with(delta),
+            ))),
+            e => e,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
-struct Binding {
-    original: crate::model::Var,
-    rename: crate::model::Var,
+pub struct Bindings {
+    /// The bound variables, innermost last. Their names are kept as hints;
+    /// [`Expr::named`] renames them where they would capture.
+    stack: Vec<crate::model::Var>,
+    substitution: Option<(crate::model::Var, crate::model::Expr)>,
 }
 
 impl Bindings {
@@ -95,9 +373,9 @@ impl Bindings {
             .map_or(HashSet::new(), |(_, body)| body.free_vars())
     }
 
-    fn with(&self, original: crate::model::Var, rename: crate::model::Var) -> Self {
+    fn with(&self, var: crate::model::Var) -> Self {
         let mut s = self.clone();
-        s.stack.push(Binding { original, rename });
+        s.stack.push(var);
         s
     }
 
@@ -107,11 +385,11 @@ impl Bindings {
             .iter()
             .rev()
             .enumerate()
-            .find(|(_, v)| &v.original == search)
+            .find(|(_, v)| *v == search)
         {
             return IndexResult::Index {
                 index: ix + 1,
-                rename: v.rename,
+                name: *v,
             };
         }
 
@@ -126,7 +404,7 @@ impl Bindings {
 
     fn get_binding_or_substitution(&self, search: crate::model::Var) -> Expr {
         match self.index(&search) {
-            IndexResult::Index { index, rename } => Expr::Var(Var::Bound(index, rename)),
+            IndexResult::Index { index, name } => Expr::Var(Var::Bound(index, name)),
             IndexResult::Free => Expr::Var(Var::Free(search)),
             IndexResult::Substitution(e) => e.de_bruijn(),
         }
@@ -137,7 +415,7 @@ enum IndexResult {
     Free,
     Index {
         index: usize,
-        rename: crate::model::Var,
+        name: crate::model::Var,
     },
     Substitution(crate::model::Expr),
 }
@@ -147,29 +425,26 @@ pub fn de_bruijn(e: &crate::model::Expr, bindings: &Bindings) -> Expr {
         crate::model::Expr::Var(var) => bindings.get_binding_or_substitution(*var),
         crate::model::Expr::Asterisk => Expr::Asterisk,
         crate::model::Expr::Square => Expr::Square,
+        crate::model::Expr::Meta(m) => Expr::Meta(*m),
+        crate::model::Expr::Hole(h) => Expr::Hole(h.clone()),
         crate::model::Expr::Lambda(lambda) => {
             let crate::model::Lambda(var, m, n) = &**lambda;
-            let mut fv = bindings.substitution_free_vars();
-            fv.extend(n.free_vars());
-            let rename = generate_free_var_gte(&fv, *var);
             Expr::Lambda(Box::new(Lambda(
-                rename,
+                *var,
                 de_bruijn(m, bindings),
-                de_bruijn(n, &bindings.with(*var, rename)),
+                de_bruijn(n, &bindings.with(*var)),
             )))
         }
         crate::model::Expr::Pi(pi) => {
             let crate::model::Pi(var, m, n) = &**pi;
-            let mut fv = bindings.substitution_free_vars();
-            fv.extend(n.free_vars());
-            let rename = generate_free_var_gte(&fv, *var);
             Expr::Pi(Box::new(Pi(
-                rename,
+                *var,
                 de_bruijn(m, bindings),
-                de_bruijn(n, &bindings.with(*var, rename)),
+                de_bruijn(n, &bindings.with(*var)),
             )))
         }
-        crate::model::Expr::Definition(crate::model::Definition(a, exprs)) => {
+        crate::model::Expr::Definition(definition) => {
+            let crate::model::Definition(a, exprs) = &**definition;
             Expr::Definition(Definition(
                 a.clone(),
                 exprs.iter().map(|e| de_bruijn(e, bindings)).collect(),
@@ -189,6 +464,8 @@ pub fn de_bruijn(e: &crate::model::Expr, bindings: &Bindings) -> Expr {
 mod tests {
     use rstest::rstest;
 
+    use super::Expr;
+
     #[rstest]
     #[case("x", "x", "z", "z")]
     #[case("%(x)(x)", "x", "z", "%(z)(z)")]
@@ -208,7 +485,7 @@ mod tests {
         #[case] expected: crate::model::Expr,
     ) {
         assert_eq!(
-            e.alpha_substitution(v, sub).de_bruijn(),
+            e.alpha_substitution(v, sub).unwrap().de_bruijn(),
             expected.de_bruijn(),
         );
     }
@@ -251,6 +528,59 @@ mod tests {
         assert_ne!(a.de_bruijn(), b.de_bruijn());
     }
 
+    #[rstest]
+    #[case("$y:(*).(%(y)(x))", "x", "%(x)(x)")]
+    #[case("$y:(*).($x:(*).(%(y)(x)))", "z", "$x:(*).(%(z)(x))")]
+    #[case("$y:(*).($x:(*).(%(y)(x)))", "x", "$u:(*).(%(x)(u))")]
+    fn instantiate(
+        #[case] binder: crate::model::Expr,
+        #[case] value: crate::model::Expr,
+        #[case] expected: crate::model::Expr,
+    ) {
+        let Expr::Lambda(l) = binder.de_bruijn() else {
+            panic!("expected lambda");
+        };
+        let result: crate::model::Expr = l.2.instantiate(&value.de_bruijn()).try_into().unwrap();
+        assert_eq!(result.de_bruijn(), expected.de_bruijn());
+    }
+
+    #[rstest]
+    #[case("%($x:(*).(x))(y)", "y")]
+    #[case("%(%($x:(*).($y:(*).(x)))(a))(b)", "a")]
+    #[case("$z:(*).(%($x:(*).($y:(*).(x)))(y))", "$z:(*).($u:(*).(y))")]
+    #[case("%($f:(*).(%(f)(%(f)(x))))($y:(*).(y))", "x")]
+    #[case("?x:(%($y:(*).(y))(A)).(x)", "?x:(A).(x)")]
+    fn normalize(#[case] e: crate::model::Expr, #[case] expected: crate::model::Expr) {
+        assert_eq!(e.de_bruijn().normalize(), expected.de_bruijn());
+    }
+
+    #[rstest]
+    #[case("%(f)(x)", "x")]
+    #[case("$y:(*).(%(x)(y))", "x")]
+    #[case("$x:(*).(%(x)(y))", "x")]
+    fn close_open(#[case] e: crate::model::Expr, #[case] var: crate::model::Var) {
+        let e = e.de_bruijn();
+        assert_eq!(e.close(var).open(var), e);
+        assert!(!e.close(var).free_vars().contains(&var));
+    }
+
+    #[test]
+    fn simultaneous_substitution() {
+        let e: crate::model::Expr = "implies[(A),(B)]".parse().unwrap();
+        let swapped = e.de_bruijn().substitute(&[
+            (
+                crate::model::Var('A'),
+                crate::model::Expr::Var(crate::model::Var('B')).de_bruijn(),
+            ),
+            (
+                crate::model::Var('B'),
+                crate::model::Expr::Var(crate::model::Var('A')).de_bruijn(),
+            ),
+        ]);
+        let expected: crate::model::Expr = "implies[(B),(A)]".parse().unwrap();
+        assert_eq!(swapped, expected.de_bruijn());
+    }
+
     #[rstest]
     #[test]
     fn homework_cases() {
@@ -267,4 +597,22 @@ mod tests {
             eprintln!("b & c");
         }
     }
+
+    #[test]
+    fn naming_fails_when_letters_run_out() {
+        let body = ('a'..='z')
+            .chain('A'..='Z')
+            .map(|c| Expr::Var(super::Var::Free(crate::model::Var(c))))
+            .fold(
+                Expr::Var(super::Var::Bound(1, crate::model::Var('a'))),
+                |f, a| Expr::Application(Box::new(super::Application(f, a))),
+            );
+        let e = Expr::Lambda(Box::new(super::Lambda(
+            crate::model::Var('a'),
+            Expr::Asterisk,
+            body,
+        )));
+        let error = crate::model::Expr::try_from(e).unwrap_err();
+        assert!(error.contains("all 52 letters are in use"), "{error}");
+    }
 }
@@ -0,0 +1,736 @@
+//! Dedukti import and export.
+//!
+//! λD is encoded in the λΠ-calculus modulo rewriting as a pure type system
+//! with the sorts `star` and `box`. A type of sort `s` is a code of type
+//! `Univ s` and its terms have type `Term s a`; `*` is the code `univ`,
+//! products are `prod s1 s2 a (x : Term s1 a => b)`, and `Term` decodes
+//! `prod` into a Dedukti product. Primitive definitions become declarations,
+//! the others a `def` with one rewrite rule taking the parameters as pattern
+//! variables.
+//!
+//! The import accepts the subset produced by the export, so books can be
+//! exchanged both ways.
+
+use std::fmt::Write;
+
+use crate::{
+    book::{Block, Book},
+    export::{Identifiers, Level, parenthesize},
+    model::{Application, Definition, Expr, Lambda, Pi, Var, generate_free_var_gte},
+    rule::Resolver,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+const PRELUDE: &str = "\
+(; λD as a pure type system ;)
+Sort : Type.
+star : Sort.
+box : Sort.
+Univ : Sort -> Type.
+def Term : s : Sort -> Univ s -> Type.
+univ : Univ box.
+prod : s1 : Sort -> s2 : Sort -> a : Univ s1 -> (Term s1 a -> Univ s2) -> Univ s2.
+[] Term box univ --> Univ star.
+[s1, s2, a, b] Term _ (prod s1 s2 a b) --> x : Term s1 a -> Term s2 (b x).
+";
+
+const RESERVED: &[&str] = &[
+    "Sort",
+    "star",
+    "box",
+    "Univ",
+    "Term",
+    "univ",
+    "prod",
+    "Type",
+    "def",
+    "thm",
+    "inj",
+    "injective",
+    "defac",
+    "defacu",
+    "private",
+    "require",
+    "assert",
+    "eval",
+    "infer",
+    "check",
+];
+
+/// Names that are not plain identifiers or clash with the prelude or a
+/// keyword are quoted with `{| |}`.
+const IDENTIFIERS: Identifiers = Identifiers {
+    keywords: RESERVED,
+    escape: |name| format!("{{|{}|}}", name.replace("|}", "|_}")),
+};
+
+fn sanitize(name: &str) -> String {
+    IDENTIFIERS.sanitize(name)
+}
+
+fn sort_name(s: &Expr) -> Result<&'static str> {
+    match s {
+        Expr::Asterisk => Ok("star"),
+        Expr::Square => Ok("box"),
+        s => Err(format!("{s} is not a sort")),
+    }
+}
+
+struct Export<'r> {
+    resolver: &'r mut Resolver,
+    definitions: usize,
+}
+
+impl Export<'_> {
+    /// The sort of the type `a` in `context`.
+    fn sort(&mut self, context: usize, a: &Expr) -> Result<&'static str> {
+        let anchor = self.resolver.anchor(self.definitions, context)?;
+        let j = self.resolver.derive(anchor, a)?;
+        sort_name(&self.resolver.judgements[j].n).map_err(|_| format!("{a} is not a type"))
+    }
+
+    /// The Dedukti type of the terms of type `a` in `context`.
+    fn ty(&mut self, context: usize, a: &Expr, level: Level) -> Result<String> {
+        let (s, own) = match a {
+            Expr::Asterisk => ("Univ star".to_string(), Level::Application),
+            Expr::Square => ("Univ box".to_string(), Level::Application),
+            a => (
+                format!(
+                    "Term {} {}",
+                    self.sort(context, a)?,
+                    self.term(context, a, Level::Atom)?
+                ),
+                Level::Application,
+            ),
+        };
+        Ok(parenthesize(s, own, level))
+    }
+
+    /// Extends `context` with `x : a`, renaming `x` in `body` if it is
+    /// already declared.
+    fn extend(
+        &mut self,
+        context: usize,
+        x: Var,
+        a: &Expr,
+        body: &Expr,
+    ) -> Result<(usize, Var, Expr)> {
+        let declared = self
+            .resolver
+            .declarations(context)
+            .into_iter()
+            .map(|(v, _)| v)
+            .collect::<std::collections::HashSet<_>>();
+        let (x, body) = if declared.contains(&x) {
+            let mut avoid = body.free_vars();
+            avoid.extend(declared);
+            let y = generate_free_var_gte(&avoid, x)?;
+            (y, body.alpha_substitution(x, y.into())?)
+        } else {
+            (x, body.clone())
+        };
+        let context = self.resolver.context.create(context, (x, a.clone()));
+        Ok((context, x, body))
+    }
+
+    fn term(&mut self, context: usize, e: &Expr, level: Level) -> Result<String> {
+        let (s, own) = match e {
+            Expr::Var(x) => (x.0.to_string(), Level::Atom),
+            Expr::Asterisk => ("univ".to_string(), Level::Atom),
+            Expr::Square | Expr::Meta(_) | Expr::Hole(_) => {
+                return Err(format!("{e} cannot be exported as a term"));
+            }
+            Expr::Definition(d) if d.1.is_empty() => (sanitize(&d.0), Level::Atom),
+            Expr::Definition(d) => {
+                let mut s = sanitize(&d.0);
+                for arg in &d.1 {
+                    write!(s, " {}", self.term(context, arg, Level::Atom)?).unwrap();
+                }
+                (s, Level::Application)
+            }
+            Expr::Application(a) => (
+                format!(
+                    "{} {}",
+                    self.term(context, &a.0, Level::Application)?,
+                    self.term(context, &a.1, Level::Atom)?
+                ),
+                Level::Application,
+            ),
+            Expr::Pi(pi) => {
+                let s1 = self.sort(context, &pi.1)?;
+                let (inner, x, body) = self.extend(context, pi.0, &pi.1, &pi.2)?;
+                let s2 = self.sort(inner, &body)?;
+                (
+                    format!(
+                        "prod {s1} {s2} {} ({} : {} => {})",
+                        self.term(context, &pi.1, Level::Atom)?,
+                        x.0,
+                        self.ty(context, &pi.1, Level::Application)?,
+                        self.term(inner, &body, Level::Binder)?
+                    ),
+                    Level::Application,
+                )
+            }
+            Expr::Lambda(l) => {
+                let (inner, x, body) = self.extend(context, l.0, &l.1, &l.2)?;
+                (
+                    format!(
+                        "{} : {} => {}",
+                        x.0,
+                        self.ty(context, &l.1, Level::Application)?,
+                        self.term(inner, &body, Level::Binder)?
+                    ),
+                    Level::Binder,
+                )
+            }
+        };
+        Ok(parenthesize(s, own, level))
+    }
+}
+
+impl Resolver {
+    /// The definitions on the path `definitions` as a Dedukti file, including
+    /// the prelude encoding λD.
+    pub fn to_dedukti(&mut self, definitions: usize) -> Result<String> {
+        let entries = self
+            .definitions
+            .path(definitions)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut export = Export {
+            resolver: self,
+            definitions,
+        };
+
+        let mut out = PRELUDE.to_string();
+        for entry in entries {
+            let name = sanitize(&entry.name);
+            let params = export.resolver.declarations(entry.context);
+            let mut ty = String::new();
+            let mut context = 0;
+            for (x, a) in &params {
+                write!(
+                    ty,
+                    "{} : {} -> ",
+                    x.0,
+                    export.ty(context, a, Level::Application)?
+                )
+                .unwrap();
+                context = export.resolver.context.create(context, (*x, a.clone()));
+            }
+            ty.push_str(&export.ty(entry.context, &entry.n, Level::Binder)?);
+
+            match &entry.m {
+                None => writeln!(out, "\n{name} : {ty}.").unwrap(),
+                Some(m) => {
+                    let vars = params.iter().map(|(x, _)| x.0.to_string());
+                    let lhs = std::iter::once(name.clone())
+                        .chain(vars.clone())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    let rhs = export.term(entry.context, m, Level::Binder)?;
+                    writeln!(out, "\ndef {name} : {ty}.").unwrap();
+                    writeln!(
+                        out,
+                        "[{}] {lhs} --> {rhs}.",
+                        vars.collect::<Vec<_>>().join(", ")
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl Book {
+    /// Checks the book and exports it to Dedukti.
+    pub fn to_dedukti(&self) -> Result<String> {
+        let (mut resolver, anchor) = self.check().map_err(|e| e.to_string())?;
+        let definitions = resolver.judgements[anchor].definitions;
+        resolver.to_dedukti(definitions)
+    }
+
+    /// Reads a book from the Dedukti subset produced by
+    /// [`Resolver::to_dedukti`].
+    pub fn from_dedukti(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: lex(s)?,
+            pos: 0,
+        };
+        let mut import = Import { blocks: vec![] };
+        while !parser.at_end() {
+            parser.item(&mut import)?;
+        }
+        import
+            .blocks
+            .into_iter()
+            .map(|(block, primitive)| match block.m {
+                None if !primitive => Err(format!(
+                    "{} is declared with def but has no rule",
+                    block.name
+                )),
+                _ => Ok(block),
+            })
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// An identifier and whether it was quoted with `{| |}`.
+    Ident(String, bool),
+    Colon,
+    Dot,
+    Comma,
+    Arrow,
+    FatArrow,
+    Rewrite,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+}
+
+fn lex(s: &str) -> Result<Vec<Token>> {
+    let chars = s.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || "_'!?".contains(c);
+    let starts = |i: usize, p: &str| chars[i..].iter().take(p.len()).copied().eq(p.chars());
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if starts(i, "(;") {
+            let end = (i + 2..chars.len())
+                .find(|&j| starts(j, ";)"))
+                .ok_or("unterminated comment")?;
+            i = end + 2;
+        } else if c == '#' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if starts(i, "{|") {
+            let end = (i + 2..chars.len())
+                .find(|&j| starts(j, "|}"))
+                .ok_or("unterminated quoted identifier")?;
+            tokens.push(Token::Ident(chars[i + 2..end].iter().collect(), true));
+            i = end + 2;
+        } else if is_ident(c) {
+            let start = i;
+            while i < chars.len() && is_ident(chars[i]) {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect(), false));
+        } else {
+            let (token, len) = if starts(i, "-->") {
+                (Token::Rewrite, 3)
+            } else if starts(i, "->") {
+                (Token::Arrow, 2)
+            } else if starts(i, "=>") {
+                (Token::FatArrow, 2)
+            } else {
+                let token = match c {
+                    ':' => Token::Colon,
+                    '.' => Token::Dot,
+                    ',' => Token::Comma,
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    '[' => Token::LBracket,
+                    ']' => Token::RBracket,
+                    c => return Err(format!("unexpected character {c:?}")),
+                };
+                (token, 1)
+            };
+            tokens.push(token);
+            i += len;
+        }
+    }
+    Ok(tokens)
+}
+
+/// A Dedukti term. Products without a name are non-dependent arrows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Dk {
+    /// An identifier and whether it was quoted.
+    Ident(String, bool),
+    App(Box<Dk>, Box<Dk>),
+    Pi(Option<String>, Box<Dk>, Box<Dk>),
+    Lambda(String, Box<Dk>, Box<Dk>),
+}
+
+impl Dk {
+    /// The head and arguments of an application.
+    fn spine(&self) -> (&Dk, Vec<&Dk>) {
+        match self {
+            Dk::App(f, a) => {
+                let (head, mut args) = f.spine();
+                args.push(a);
+                (head, args)
+            }
+            head => (head, vec![]),
+        }
+    }
+
+    /// Whether this is the unquoted prelude identifier `name`.
+    fn is(&self, name: &str) -> bool {
+        matches!(self, Dk::Ident(x, false) if x == name)
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn peek(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self.peek(0).cloned().ok_or("unexpected end of input")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("expected {expected:?}, found {token:?}")),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        self.quoted_ident().map(|(x, _)| x)
+    }
+
+    fn quoted_ident(&mut self) -> Result<(String, bool)> {
+        match self.next()? {
+            Token::Ident(x, quoted) => Ok((x, quoted)),
+            token => Err(format!("expected an identifier, found {token:?}")),
+        }
+    }
+
+    fn term(&mut self) -> Result<Dk> {
+        if let (Some(Token::Ident(x, _)), Some(Token::Colon)) = (self.peek(0), self.peek(1)) {
+            let x = x.clone();
+            self.pos += 2;
+            let a = self.application()?;
+            return match self.next()? {
+                Token::Arrow => Ok(Dk::Pi(Some(x), Box::new(a), Box::new(self.term()?))),
+                Token::FatArrow => Ok(Dk::Lambda(x, Box::new(a), Box::new(self.term()?))),
+                token => Err(format!("expected -> or =>, found {token:?}")),
+            };
+        }
+        let a = self.application()?;
+        if self.peek(0) == Some(&Token::Arrow) {
+            self.pos += 1;
+            return Ok(Dk::Pi(None, Box::new(a), Box::new(self.term()?)));
+        }
+        Ok(a)
+    }
+
+    fn application(&mut self) -> Result<Dk> {
+        let mut e = self.atom()?;
+        while matches!(self.peek(0), Some(Token::Ident(..) | Token::LParen)) {
+            e = Dk::App(Box::new(e), Box::new(self.atom()?));
+        }
+        Ok(e)
+    }
+
+    fn atom(&mut self) -> Result<Dk> {
+        match self.next()? {
+            Token::Ident(x, quoted) => Ok(Dk::Ident(x, quoted)),
+            Token::LParen => {
+                let e = self.term()?;
+                self.expect(Token::RParen)?;
+                Ok(e)
+            }
+            token => Err(format!("expected a term, found {token:?}")),
+        }
+    }
+
+    fn item(&mut self, import: &mut Import) -> Result<()> {
+        if self.peek(0) == Some(&Token::LBracket) {
+            self.pos += 1;
+            let mut vars = vec![];
+            while self.peek(0) != Some(&Token::RBracket) {
+                vars.push(self.ident()?);
+                if self.peek(0) == Some(&Token::Comma) {
+                    self.pos += 1;
+                }
+            }
+            self.pos += 1;
+            let lhs = self.term()?;
+            self.expect(Token::Rewrite)?;
+            let rhs = self.term()?;
+            self.expect(Token::Dot)?;
+            return import.rule(&vars, &lhs, &rhs);
+        }
+
+        let primitive = self.peek(0) != Some(&Token::Ident("def".to_string(), false));
+        if !primitive {
+            self.pos += 1;
+        }
+        let (name, quoted) = self.quoted_ident()?;
+        self.expect(Token::Colon)?;
+        let ty = self.term()?;
+        self.expect(Token::Dot)?;
+        if !quoted && RESERVED.contains(&name.as_str()) {
+            return Ok(());
+        }
+        import.declaration(name, primitive, &ty)
+    }
+}
+
+struct Import {
+    /// Declared blocks, and whether they are primitive.
+    blocks: Vec<(Block, bool)>,
+}
+
+fn var(x: &str) -> Result<Var> {
+    x.parse::<Var>()
+        .map_err(|_| format!("{x} is not a single-letter variable"))
+}
+
+impl Import {
+    fn arity(&self, name: &str) -> Option<usize> {
+        self.blocks
+            .iter()
+            .find(|(b, _)| b.name == name)
+            .map(|(b, _)| b.context.len())
+    }
+
+    fn declaration(&mut self, name: String, primitive: bool, ty: &Dk) -> Result<()> {
+        let mut context = vec![];
+        let mut scope = vec![];
+        let mut ty = ty;
+        while let Dk::Pi(x, a, b) = ty {
+            let x = x
+                .as_deref()
+                .ok_or_else(|| format!("the parameters of {name} must be named"))?;
+            context.push((var(x)?, self.ty(&scope, a)?));
+            scope.push((x.to_string(), var(x)?));
+            ty = b;
+        }
+        let n = self.ty(&scope, ty)?;
+        self.blocks.push((
+            Block {
+                context,
+                name,
+                m: None,
+                n,
+            },
+            primitive,
+        ));
+        Ok(())
+    }
+
+    fn rule(&mut self, vars: &[String], lhs: &Dk, rhs: &Dk) -> Result<()> {
+        let (Dk::Ident(name, quoted), args) = lhs.spine() else {
+            return Err("the left-hand side of a rule must be a definition".to_string());
+        };
+        if !quoted && RESERVED.contains(&name.as_str()) {
+            return Ok(());
+        }
+        let Some(i) = self.blocks.iter().position(|(b, _)| &b.name == name) else {
+            return Err(format!("rule for undeclared {name}"));
+        };
+        let (block, primitive) = &self.blocks[i];
+        if *primitive || block.m.is_some() {
+            return Err(format!(
+                "{name} must be declared with def and have one rule"
+            ));
+        }
+        let params = args
+            .iter()
+            .map(|a| match a {
+                Dk::Ident(x, _) if vars.contains(x) => Ok(x.clone()),
+                _ => Err(format!("the arguments of {name} must be pattern variables")),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if params.len() != block.context.len() {
+            return Err(format!(
+                "the rule for {name} takes {} arguments, expected {}",
+                params.len(),
+                block.context.len()
+            ));
+        }
+        let scope = params
+            .into_iter()
+            .zip(block.context.iter().map(|(x, _)| *x))
+            .collect::<Vec<_>>();
+        let m = self.term(&scope, rhs)?;
+        self.blocks[i].0.m = Some(m);
+        Ok(())
+    }
+
+    /// Decodes a type `Univ s` or `Term s a`.
+    fn ty(&self, scope: &[(String, Var)], t: &Dk) -> Result<Expr> {
+        match t.spine() {
+            (u, args) if u.is("Univ") => match args.as_slice() {
+                [s] if s.is("star") => Ok(Expr::Asterisk),
+                [s] if s.is("box") => Ok(Expr::Square),
+                _ => Err("expected Univ star or Univ box".to_string()),
+            },
+            (t, args) if t.is("Term") && args.len() == 2 => self.term(scope, args[1]),
+            _ => Err(format!("expected a type Univ s or Term s a, found {t:?}")),
+        }
+    }
+
+    fn term(&self, scope: &[(String, Var)], t: &Dk) -> Result<Expr> {
+        let bind = |x: &str| -> Result<Vec<(String, Var)>> {
+            let mut scope = scope.to_vec();
+            scope.push((x.to_string(), var(x)?));
+            Ok(scope)
+        };
+
+        match t {
+            Dk::Lambda(x, a, b) => {
+                Ok(Lambda(var(x)?, self.ty(scope, a)?, self.term(&bind(x)?, b)?).into())
+            }
+            Dk::Pi(..) => Err("a product of Dedukti is not a λD term".to_string()),
+            Dk::Ident(..) | Dk::App(..) => {
+                let (head, args) = t.spine();
+                let Dk::Ident(h, _) = head else {
+                    return Err("only variables and definitions can be applied".to_string());
+                };
+                let args = args.into_iter().collect::<Vec<_>>();
+
+                if head.is("prod") {
+                    let [_, _, a, Dk::Lambda(x, _, b)] = args.as_slice() else {
+                        return Err("prod expects two sorts, a type and a function".to_string());
+                    };
+                    return Ok(Pi(var(x)?, self.term(scope, a)?, self.term(&bind(x)?, b)?).into());
+                }
+
+                let (mut e, rest) = if head.is("univ") {
+                    (Expr::Asterisk, args.as_slice())
+                } else if let Some((_, x)) = scope.iter().rev().find(|(y, _)| y == h) {
+                    (Expr::Var(*x), args.as_slice())
+                } else if let Some(arity) = self.arity(h) {
+                    let (own, rest) = args.split_at(arity.min(args.len()));
+                    let own = own
+                        .iter()
+                        .map(|a| self.term(scope, a))
+                        .collect::<Result<Vec<_>>>()?;
+                    (Definition(h.clone(), own).into(), rest)
+                } else {
+                    return Err(format!("unknown identifier {h}"));
+                };
+                for a in rest {
+                    e = Application(e, self.term(scope, a)?).into();
+                }
+                Ok(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let book: Book = include_str!("../check/def2").parse().unwrap();
+        let dk = book.to_dedukti().unwrap();
+        assert!(dk.starts_with(PRELUDE));
+        assert!(dk.contains(
+            "\ndef implies : A : Univ star -> B : Univ star -> Univ star.\n\
+             [A, B] implies A B --> prod star star A (a : Term star A => B).\n"
+        ));
+        assert!(dk.contains("[] contra --> prod box star univ (x : Univ star => x).\n"));
+
+        let imported = Book::from_dedukti(&dk).unwrap();
+        assert_eq!(imported.0.len(), book.0.len());
+        for (a, b) in imported.0.iter().zip(&book.0) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.context.len(), b.context.len());
+            for ((x, t), (y, u)) in a.context.iter().zip(&b.context) {
+                assert_eq!(x, y);
+                assert!(t.alpha_eq(u), "{t} {u}");
+            }
+            assert!(a.m.as_ref().unwrap().alpha_eq(b.m.as_ref().unwrap()));
+            assert!(a.n.alpha_eq(&b.n));
+        }
+        imported.check().unwrap();
+    }
+
+    #[test]
+    fn reserved_names_round_trip() {
+        let book: Book = "\
+            def2\n0\nprod\n#\n*\nedef2\n\
+            def2\n1\nx\nprod[]\nTerm\nx\nprod[]\nedef2\nEND"
+            .parse()
+            .unwrap();
+        let dk = book.to_dedukti().unwrap();
+        assert!(dk.contains("\n{|prod|} : Univ star.\n"), "{dk}");
+        let imported = Book::from_dedukti(&dk).unwrap();
+        assert_eq!(imported, book);
+        imported.check().unwrap();
+    }
+
+    #[test]
+    fn import() {
+        let dk = format!(
+            "{PRELUDE}\n\
+             #NAME logic.\n\
+             {{|false thing|}} : Univ star.\n\
+             (; a primitive with a parameter ;)\n\
+             elim : P : Univ star -> f : Term star {{|false thing|}} -> Term star P.\n\
+             def id : A : Univ star -> Term star (prod star star A (x : Term star A => A)).\n\
+             [B] id B --> y : Term star B => y.\n"
+        );
+        let book = Book::from_dedukti(&dk).unwrap();
+        let expected: Book = "def2\n0\nfalse thing\n#\n*\nedef2\nEND".parse().unwrap();
+        assert_eq!(book.0[0], expected.0[0]);
+        assert!(matches!(
+            Book::from_dedukti(&format!("{PRELUDE}\nelim : Term star univ -> Univ star.")),
+            Err(e) if e.contains("must be named")
+        ));
+        let id = book.get("id").unwrap();
+        assert_eq!(id.context, vec![(Var('A'), Expr::Asterisk)]);
+        assert!(
+            id.m.as_ref()
+                .unwrap()
+                .alpha_eq(&"$y:(A).(y)".parse().unwrap())
+        );
+        book.check().unwrap();
+    }
+
+    #[rstest]
+    #[case("foo : Univ star.\nbar : Term star baz.", "unknown identifier baz")]
+    #[case(
+        "foo : xy : Univ star -> Univ star.",
+        "xy is not a single-letter variable"
+    )]
+    #[case("def foo : Univ star.", "foo is declared with def but has no rule")]
+    #[case("[x] foo x --> x.", "rule for undeclared foo")]
+    #[case("foo : Univ star", "unexpected end of input")]
+    #[case("foo : Univ star. $", "unexpected character")]
+    #[case("foo : Type.", "expected a type")]
+    fn import_errors(#[case] dk: &str, #[case] message: &str) {
+        let error = Book::from_dedukti(dk).unwrap_err();
+        assert!(error.contains(message), "{error}");
+    }
+
+    #[rstest]
+    #[case("prod", "{|prod|}")]
+    #[case("false thing", "{|false thing|}")]
+    fn sanitize_names(#[case] name: &str, #[case] expected: &str) {
+        assert_eq!(sanitize(name), expected);
+    }
+}
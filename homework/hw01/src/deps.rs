@@ -0,0 +1,306 @@
+//! Dependencies between definitions.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+};
+
+use crate::{book::Book, model::Expr, rule::Resolver};
+
+/// Adds the names of the definitions instantiated in `e` to `names`.
+pub fn references(e: &Expr, names: &mut BTreeSet<String>) {
+    match e {
+        Expr::Var(_) | Expr::Asterisk | Expr::Square | Expr::Meta(_) | Expr::Hole(_) => {}
+        Expr::Lambda(l) => {
+            references(&l.1, names);
+            references(&l.2, names);
+        }
+        Expr::Pi(pi) => {
+            references(&pi.1, names);
+            references(&pi.2, names);
+        }
+        Expr::Definition(d) => {
+            names.insert(d.0.clone());
+            for arg in &d.1 {
+                references(arg, names);
+            }
+        }
+        Expr::Application(a) => {
+            references(&a.0, names);
+            references(&a.1, names);
+        }
+    }
+}
+
+impl Resolver {
+    /// Definitions referenced by the parameter types, body and type of the
+    /// definition `name` visible from `definitions`.
+    pub fn dependencies(&self, definitions: usize, name: &str) -> Option<BTreeSet<String>> {
+        let d = self.definitions.resolve(definitions, |d| d.name == name)?;
+        let mut names = BTreeSet::new();
+        for (_, a) in self.context.path(d.context) {
+            references(a, &mut names);
+        }
+        if let Some(m) = &d.m {
+            references(m, &mut names);
+        }
+        references(&d.n, &mut names);
+        Some(names)
+    }
+
+    /// Primitive definitions that `name` transitively depends on, including
+    /// `name` itself if it is primitive.
+    pub fn axioms(&self, definitions: usize, name: &str) -> Option<BTreeSet<String>> {
+        let mut axioms = BTreeSet::new();
+        let mut visited = BTreeSet::from([name.to_string()]);
+        let mut stack = vec![name.to_string()];
+
+        while let Some(name) = stack.pop() {
+            let d = self.definitions.resolve(definitions, |d| d.name == name)?;
+            if d.m.is_none() {
+                axioms.insert(name.clone());
+            }
+            for dependency in self.dependencies(definitions, &name)? {
+                if visited.insert(dependency.clone()) {
+                    stack.push(dependency);
+                }
+            }
+        }
+
+        Some(axioms)
+    }
+}
+
+/// The references between the blocks of a book: `edges[i]` holds the
+/// blocks that block `i` refers to. References to names that no block
+/// defines are left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Graph {
+    pub names: Vec<String>,
+    pub edges: Vec<BTreeSet<usize>>,
+}
+
+impl Graph {
+    pub fn new(book: &Book) -> Self {
+        let mut index = HashMap::new();
+        for (i, block) in book.0.iter().enumerate() {
+            index.entry(block.name.as_str()).or_insert(i);
+        }
+
+        let edges = book
+            .0
+            .iter()
+            .map(|block| {
+                let mut names = BTreeSet::new();
+                for (_, a) in &block.context {
+                    references(a, &mut names);
+                }
+                if let Some(m) = &block.m {
+                    references(m, &mut names);
+                }
+                references(&block.n, &mut names);
+                names
+                    .iter()
+                    .filter_map(|name| index.get(name.as_str()).copied())
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            names: book.0.iter().map(|b| b.name.clone()).collect(),
+            edges,
+        }
+    }
+
+    /// Blocks that no other block refers to.
+    pub fn unused(&self) -> Vec<usize> {
+        let mut used = vec![false; self.names.len()];
+        for (i, edges) in self.edges.iter().enumerate() {
+            for &j in edges {
+                if i != j {
+                    used[j] = true;
+                }
+            }
+        }
+        (0..self.names.len()).filter(|&i| !used[i]).collect()
+    }
+
+    /// Blocks that none of `roots` depend on, directly or transitively.
+    pub fn unreachable(&self, roots: &[usize]) -> Vec<usize> {
+        let mut reachable = vec![false; self.names.len()];
+        let mut stack = roots.to_vec();
+        while let Some(i) = stack.pop() {
+            if !std::mem::replace(&mut reachable[i], true) {
+                stack.extend(&self.edges[i]);
+            }
+        }
+        (0..self.names.len()).filter(|&i| !reachable[i]).collect()
+    }
+
+    /// Blocks ordered so that every block comes after the blocks it refers
+    /// to, keeping the original order where possible. Fails on a cycle.
+    pub fn topological_order(&self) -> Result<Vec<usize>, String> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            New,
+            Active,
+            Done,
+        }
+
+        fn visit(
+            graph: &Graph,
+            i: usize,
+            state: &mut [State],
+            order: &mut Vec<usize>,
+        ) -> Result<(), String> {
+            match state[i] {
+                State::Done => return Ok(()),
+                State::Active => {
+                    return Err(format!("{} depends on itself", graph.names[i]));
+                }
+                State::New => {}
+            }
+            state[i] = State::Active;
+            for &j in &graph.edges[i] {
+                visit(graph, j, state, order)?;
+            }
+            state[i] = State::Done;
+            order.push(i);
+            Ok(())
+        }
+
+        let mut state = vec![State::New; self.names.len()];
+        let mut order = Vec::with_capacity(self.names.len());
+        for i in 0..self.names.len() {
+            visit(self, i, &mut state, &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// The graph in Graphviz DOT format, with an edge from every block to
+    /// the blocks it refers to.
+    pub fn dot(&self) -> String {
+        let mut dot = String::from("digraph book {\n");
+        for name in &self.names {
+            writeln!(dot, "    {name:?};").unwrap();
+        }
+        for (i, edges) in self.edges.iter().enumerate() {
+            for &j in edges {
+                writeln!(dot, "    {:?} -> {:?};", self.names[i], self.names[j]).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl Book {
+    /// The blocks reordered by [`Graph::topological_order`].
+    pub fn sorted(&self) -> Result<Book, String> {
+        let order = Graph::new(self).topological_order()?;
+        Ok(Book(order.into_iter().map(|i| self.0[i].clone()).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::book::Book;
+
+    use super::*;
+
+    const CLASSICAL: &str = "
+        def2
+        1
+        A
+        *
+        dne
+        #
+        ?u:(not[(not[(A)])]).(A)
+        edef2
+
+        def2
+        2
+        A
+        *
+        u
+        not[(not[(A)])]
+        dne_el
+        %(dne[(A)])(u)
+        A
+        edef2
+    ";
+
+    #[rstest]
+    #[case("implies", &[])]
+    #[case("a2_fig118", &[])]
+    #[case("dne", &["dne"])]
+    #[case("dne_el", &["dne"])]
+    fn axioms(#[case] name: &str, #[case] expected: &[&str]) {
+        let mut book: Book = include_str!("../check/def2").parse().unwrap();
+        book.0.extend(CLASSICAL.parse::<Book>().unwrap().0);
+        let (resolver, anchor) = book.check().unwrap();
+        let definitions = resolver.judgements[anchor].definitions;
+
+        let axioms = resolver.axioms(definitions, name).unwrap();
+        assert_eq!(axioms, expected.iter().map(|s| s.to_string()).collect());
+    }
+
+    fn names(graph: &Graph, blocks: Vec<usize>) -> Vec<&str> {
+        blocks
+            .into_iter()
+            .map(|i| graph.names[i].as_str())
+            .collect()
+    }
+
+    #[test]
+    fn graph() {
+        let book: Book = include_str!("../check/def2").parse().unwrap();
+        let graph = Graph::new(&book);
+
+        let not = graph.names.iter().position(|n| n == "not").unwrap();
+        assert_eq!(
+            names(&graph, graph.edges[not].iter().copied().collect()),
+            ["implies", "contra"]
+        );
+
+        assert_eq!(
+            names(&graph, graph.unused()),
+            [
+                "implies_el",
+                "contra_in",
+                "contra_el",
+                "a3_fig118",
+                "and_in"
+            ]
+        );
+        let a3 = graph.names.iter().position(|n| n == "a3_fig118").unwrap();
+        assert_eq!(
+            names(&graph, graph.unreachable(&[a3])),
+            ["implies_el", "contra_in", "contra_el", "and", "and_in"]
+        );
+        assert!(graph.dot().contains("    \"not\" -> \"implies\";\n"));
+    }
+
+    #[test]
+    fn topological_order() {
+        let book: Book = include_str!("../check/def2").parse().unwrap();
+        assert_eq!(book.sorted().unwrap(), book);
+
+        let reversed = Book(book.0.iter().rev().cloned().collect());
+        let sorted = reversed.sorted().unwrap();
+        assert_ne!(sorted, reversed);
+        sorted.check().unwrap();
+
+        let mut cyclic = book.clone();
+        cyclic.0[0].n = "not[(*)]".parse().unwrap();
+        assert!(cyclic.sorted().unwrap_err().contains("depends on itself"));
+    }
+
+    #[test]
+    fn unknown_definition() {
+        let resolver = Resolver::new();
+        assert_eq!(resolver.axioms(0, "implies"), None);
+    }
+}
@@ -0,0 +1,451 @@
+use crate::{
+    de_bruijn,
+    model::{Definition, Expr, Var, generate_free_var_gte},
+    rule::{Judgement, Resolver},
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+/// Derived rules. Everything here is built from the primitive rules in
+/// [`Resolver`], so a derived judgement is always backed by a derivation
+/// recorded in `judgements`.
+impl Resolver {
+    /// Declarations of a context, outermost first.
+    pub fn declarations(&self, context: usize) -> Vec<(Var, Expr)> {
+        self.context.path(context).into_iter().cloned().collect()
+    }
+
+    /// Unfolds an instance of a non-primitive definition visible from
+    /// `definitions`.
+    pub fn unfold(&self, definitions: usize, d: &de_bruijn::Definition) -> Option<de_bruijn::Expr> {
+        let entry = self.definitions.resolve(definitions, |e| e.name == d.0)?;
+        let body = entry.m.as_ref()?;
+        let params = self.context.path(entry.context);
+        if params.len() != d.1.len() {
+            return None;
+        }
+        let substitution = params
+            .iter()
+            .zip(&d.1)
+            .map(|((v, _), e)| (*v, e.clone()))
+            .collect::<Vec<_>>();
+        Some(body.de_bruijn().substitute(&substitution))
+    }
+
+    /// Weak head normal form, unfolding definitions in head position.
+    pub fn whnf(&self, definitions: usize, e: &Expr) -> Result<Expr> {
+        e.de_bruijn()
+            .whnf_with(&|d| self.unfold(definitions, d))
+            .try_into()
+    }
+
+    /// Beta-delta convertibility, up to alpha-equivalence.
+    pub fn convertible(&self, definitions: usize, a: &Expr, b: &Expr) -> bool {
+        let delta = |d: &de_bruijn::Definition| self.unfold(definitions, d);
+        a.alpha_eq(b)
+            || a.de_bruijn().normalize_with(&delta) == b.de_bruijn().normalize_with(&delta)
+    }
+
+    /// A judgement deriving `* : @` in the given environment and context.
+    /// Reuses one from the log when possible and otherwise weakens one into
+    /// the context.
+    pub fn anchor(&mut self, definitions: usize, context: usize) -> Result<usize> {
+        if let Some(j) = self.judgements.iter().rposition(|j| {
+            j.definitions == definitions
+                && j.context == context
+                && j.m == Expr::Asterisk
+                && j.n == Expr::Square
+        }) {
+            return Ok(j);
+        }
+
+        let Some(entry) = self.context.get(context) else {
+            return Err(format!(
+                "no judgement derives {} : {} in the empty context of this environment",
+                Expr::Asterisk,
+                Expr::Square
+            ));
+        };
+        let (parent, (var, ty)) = (entry.parent_index, entry.value.clone());
+        let anchor = self.anchor(definitions, parent)?;
+        let ty = self.derive_type(anchor, &ty)?;
+        Ok(self.weak(anchor, ty, var))
+    }
+
+    /// Derives a judgement `M : N` for `e` in the environment and context of
+    /// `anchor`, which must derive `* : @`. The derived `M` is
+    /// alpha-equivalent to `e`, except that instances of definitions may omit
+    /// leading arguments, which are then inferred by unification.
+    pub fn derive(&mut self, anchor: usize, e: &Expr) -> Result<usize> {
+        let Judgement {
+            definitions,
+            context,
+            ..
+        } = self.judgements[anchor];
+
+        match e {
+            Expr::Asterisk => Ok(anchor),
+            Expr::Square => Err(format!("{} has no type", Expr::Square)),
+            Expr::Meta(_) => Err(format!("the metavariable {e} is unsolved")),
+            Expr::Hole(_) => Err(format!("the hole {e} is not filled in")),
+            Expr::Var(x) => {
+                let ix = self
+                    .context
+                    .path(context)
+                    .iter()
+                    .rposition(|(v, _)| v == x)
+                    .ok_or_else(|| format!("{x} is not declared in the context"))?;
+                Ok(self.sp(anchor, ix))
+            }
+            Expr::Pi(pi) => {
+                let (x, body) = self.fresh_binder(context, pi.0, &pi.2)?;
+                let a = self.derive_type(anchor, &pi.1)?;
+                let inner = self.weak(anchor, a, x);
+                let b = self.derive_type(inner, &body)?;
+                Ok(self.form(a, b))
+            }
+            Expr::Lambda(lambda) => {
+                let (x, body) = self.fresh_binder(context, lambda.0, &lambda.2)?;
+                let a = self.derive_type(anchor, &lambda.1)?;
+                let inner = self.weak(anchor, a, x);
+                let m = self.derive(inner, &body)?;
+                let ty = self.judgements[m].n.clone();
+                let b = self.derive_type(inner, &ty)?;
+                let pi = self.form(a, b);
+                Ok(self.abst(m, pi))
+            }
+            Expr::Application(application) => {
+                let f = self.derive(anchor, &application.0)?;
+                let f = self.expect_pi(anchor, f)?;
+                let Expr::Pi(pi) = &self.judgements[f].n else {
+                    unreachable!();
+                };
+                let domain = pi.1.clone();
+                let a = self.derive(anchor, &application.1)?;
+                let a = self.convert(anchor, a, &domain)?;
+                self.try_appl(f, a)
+            }
+            Expr::Definition(d) => {
+                let Some(entry) = self.definitions.resolve(definitions, |e| e.name == d.0) else {
+                    return Err(format!("unknown definition {}", d.0));
+                };
+                let params = self.declarations(entry.context);
+                if d.1.len() < params.len() {
+                    let args = self.implicit_arguments(anchor, &d.0, &params, &d.1)?;
+                    return self.derive(anchor, &Definition(d.0.clone(), args).into());
+                }
+                if params.len() != d.1.len() {
+                    return Err(format!(
+                        "{} expects {} arguments, found {}",
+                        d.0,
+                        params.len(),
+                        d.1.len()
+                    ));
+                }
+
+                let mut substitution = vec![];
+                let mut args = vec![];
+                for ((v, ty), u) in params.iter().zip(&d.1) {
+                    let j = self.derive(anchor, u)?;
+                    let j = self.convert(anchor, j, &ty.substitute(&substitution)?)?;
+                    substitution.push((*v, self.judgements[j].m.clone()));
+                    args.push(j);
+                }
+
+                self.try_inst(anchor, &args, d.0.clone())
+            }
+        }
+    }
+
+    /// Like [`Resolver::derive`], additionally requiring `e` to be a type.
+    pub fn derive_type(&mut self, anchor: usize, e: &Expr) -> Result<usize> {
+        let j = self.derive(anchor, e)?;
+        let n = &self.judgements[j].n;
+        if n.is_sort() {
+            Ok(j)
+        } else {
+            Err(format!("{e} is not a type: its type {n} is not a sort"))
+        }
+    }
+
+    /// Converts the type of `j` to `expected`, checking beta-delta
+    /// convertibility first.
+    pub fn convert(&mut self, anchor: usize, j: usize, expected: &Expr) -> Result<usize> {
+        let Judgement {
+            definitions,
+            ref m,
+            ref n,
+            ..
+        } = self.judgements[j];
+        if n.alpha_eq(expected) {
+            return Ok(j);
+        }
+        if !self.convertible(definitions, n, expected) {
+            return Err(format!("{m} has type {n}, expected {expected}"));
+        }
+        let ty = self.derive_type(anchor, expected)?;
+        Ok(self.conv(j, ty))
+    }
+
+    fn expect_pi(&mut self, anchor: usize, j: usize) -> Result<usize> {
+        let Judgement {
+            definitions,
+            ref m,
+            ref n,
+            ..
+        } = self.judgements[j];
+        if let Expr::Pi(_) = n {
+            return Ok(j);
+        }
+        let whnf = self.whnf(definitions, n)?;
+        let Expr::Pi(_) = whnf else {
+            return Err(format!(
+                "{m} is applied to an argument, but its type {n} is not a product"
+            ));
+        };
+        self.convert(anchor, j, &whnf)
+    }
+
+    /// Renames a binder that would shadow a declaration in the context, since
+    /// `var` and `weak` only accept fresh variables.
+    fn fresh_binder(&self, context: usize, var: Var, body: &Expr) -> Result<(Var, Expr)> {
+        let declared = self
+            .context
+            .path(context)
+            .into_iter()
+            .map(|(v, _)| *v)
+            .collect::<std::collections::HashSet<_>>();
+        if !declared.contains(&var) {
+            return Ok((var, body.clone()));
+        }
+        let mut avoid = body.free_vars();
+        avoid.extend(declared);
+        let fresh = generate_free_var_gte(&avoid, var)?;
+        Ok((fresh, body.alpha_substitution(var, fresh.into())?))
+    }
+
+    /// Weakens `j` into `context`, which must extend the context of `j`.
+    pub fn weak_to(&mut self, j: usize, context: usize) -> Result<usize> {
+        let Judgement {
+            definitions,
+            context: from,
+            ..
+        } = self.judgements[j];
+
+        let mut added = vec![];
+        let mut c = context;
+        while self.context.len(c) > self.context.len(from) {
+            let entry = self.context.get(c).unwrap();
+            added.push(entry.value.clone());
+            c = entry.parent_index;
+        }
+        if let Some(mismatch) = self.context_mismatch(from, c) {
+            return Err(format!(
+                "the target context does not extend the context of the judgement: {mismatch}"
+            ));
+        }
+
+        let mut j = j;
+        for (var, ty) in added.into_iter().rev() {
+            let anchor = self.anchor(definitions, self.judgements[j].context)?;
+            let ty = self.derive_type(anchor, &ty)?;
+            j = self.weak(j, ty, var);
+        }
+        Ok(j)
+    }
+
+    /// Extends the context of `j` by `declarations`, deriving `* : @` in the
+    /// extended context.
+    pub fn start(&mut self, j: usize, declarations: &[(Var, Expr)]) -> Result<usize> {
+        let Judgement {
+            definitions,
+            context,
+            ..
+        } = self.judgements[j];
+
+        let mut anchor = self.anchor(definitions, context)?;
+        for (var, ty) in declarations {
+            let context = self.judgements[anchor].context;
+            if let Some((_, declared)) = self.context.resolve(context, |(v, _)| v == var) {
+                return Err(format!(
+                    "{var} is not fresh: the context already declares {var}:{declared}"
+                ));
+            }
+            let ty = self.derive_type(anchor, ty)?;
+            anchor = self.weak(anchor, ty, *var);
+        }
+        Ok(anchor)
+    }
+
+    /// `inst` for the `d`th definition, weakening the arguments into the
+    /// context of `j` and converting them to the parameter types as needed.
+    pub fn inst_weak(&mut self, j: usize, args: &[usize], d: usize) -> Result<usize> {
+        let Judgement {
+            definitions,
+            context,
+            ..
+        } = self.judgements[j];
+
+        let Some(entry) = self.definitions.traverse(definitions, d) else {
+            return Err(format!("there is no definition with index {d}"));
+        };
+        let (name, params) = (
+            entry.value.name.clone(),
+            self.declarations(entry.value.context),
+        );
+        if params.len() != args.len() {
+            return Err(format!(
+                "{name} expects {} arguments, found {}",
+                params.len(),
+                args.len()
+            ));
+        }
+
+        let anchor = self.anchor(definitions, context)?;
+        let mut substitution = vec![];
+        let mut weakened = vec![];
+        for ((v, ty), arg) in params.iter().zip(args) {
+            if self.judgements[*arg].definitions != definitions {
+                return Err(format!(
+                    "argument {} of {name} is derived in a different definition environment",
+                    self.judgements[*arg].m
+                ));
+            }
+            let arg = self.weak_to(*arg, context)?;
+            let arg = self.convert(anchor, arg, &ty.substitute(&substitution)?)?;
+            substitution.push((*v, self.judgements[arg].m.clone()));
+            weakened.push(arg);
+        }
+
+        self.try_inst_ix(anchor, &weakened, d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::book::Book;
+
+    fn implies() -> (Resolver, usize) {
+        let mut book = Resolver::new();
+        let sort = book.sort();
+        let ab = book
+            .start(
+                sort,
+                &[(Var('A'), Expr::Asterisk), (Var('B'), Expr::Asterisk)],
+            )
+            .unwrap();
+        let ty = book.derive(ab, &"?a:(A).(B)".parse().unwrap()).unwrap();
+        let j = book.def(sort, ty, "implies".to_string());
+        (book, j)
+    }
+
+    #[rstest]
+    #[case("*", "@")]
+    #[case("?x:(*).(x)", "*")]
+    #[case("$x:(*).($y:(x).(y))", "?x:(*).(?y:(x).(x))")]
+    #[case("implies[(?x:(*).(x)),(?x:(*).(x))]", "*")]
+    #[case(
+        "$A:(*).($u:(A).($v:(implies[(A),(A)]).(%(v)(u))))",
+        "?A:(*).(?u:(A).(?v:(implies[(A),(A)]).(A)))"
+    )]
+    fn derive(#[case] e: Expr, #[case] ty: Expr) {
+        let (mut book, anchor) = implies();
+        let j = book.derive(anchor, &e).unwrap();
+        assert!(book.judgements[j].m.alpha_eq(&e));
+        assert!(book.judgements[j].n.alpha_eq(&ty));
+    }
+
+    #[rstest]
+    #[case("@", "has no type")]
+    #[case("x", "is not declared")]
+    #[case("%(*)(*)", "is not a product")]
+    #[case("implies[(*)]", "the argument * of implies has type @, expected *")]
+    #[case("implies[(*),(*),(*)]", "expects 2 arguments")]
+    #[case("$x:(*).(%($y:(x).(y))(x))", "expected")]
+    fn derive_errors(#[case] e: Expr, #[case] message: &str) {
+        let (mut book, anchor) = implies();
+        let error = book.derive(anchor, &e).unwrap_err();
+        assert!(error.contains(message), "{error}");
+    }
+
+    #[test]
+    fn elaborated_arguments_are_substituted() {
+        let book: Book = "\
+            def2\n2\nA\n*\nx\nA\ntt\nA\n*\nedef2\n\
+            def2\n2\nX\n*\ny\nX\ngg\ny\nX\nedef2\nEND"
+            .parse()
+            .unwrap();
+        let (mut book, anchor) = book.check().unwrap();
+        let declarations = [('A', "*"), ('a', "A"), ('w', "tt[(A),(a)]")]
+            .map(|(x, a)| (Var(x), a.parse().unwrap()));
+        let anchor = book.start(anchor, &declarations).unwrap();
+        let j = book
+            .derive(anchor, &"gg[(tt[(a)]),(w)]".parse().unwrap())
+            .unwrap();
+        assert_eq!(
+            book.judgements[j].m,
+            "gg[(tt[(A),(a)]),(w)]".parse().unwrap()
+        );
+        assert_eq!(book.judgements[j].n, "tt[(A),(a)]".parse().unwrap());
+    }
+
+    #[test]
+    fn shadowing_binders_are_renamed() {
+        let (mut book, anchor) = implies();
+        let a = book.start(anchor, &[(Var('x'), Expr::Asterisk)]).unwrap();
+        let e = "$x:(*).(x)".parse().unwrap();
+        let j = book.derive(a, &e).unwrap();
+        assert!(book.judgements[j].m.alpha_eq(&e));
+    }
+
+    #[test]
+    fn weak_to() {
+        let (mut book, anchor) = implies();
+        let a = book.start(anchor, &[(Var('A'), Expr::Asterisk)]).unwrap();
+        let var = book.sp(a, 0);
+        let target = book
+            .start(
+                a,
+                &[
+                    (Var('u'), "A".parse().unwrap()),
+                    (Var('v'), "implies[(A),(A)]".parse().unwrap()),
+                ],
+            )
+            .unwrap();
+        let context = book.judgements[target].context;
+        let j = book.weak_to(var, context).unwrap();
+        assert_eq!(book.judgements[j].context, context);
+        assert_eq!(book.judgements[j].m, "A".parse().unwrap());
+    }
+
+    #[test]
+    fn inst_weak() {
+        let (mut book, anchor) = implies();
+        let a = book.start(anchor, &[(Var('A'), Expr::Asterisk)]).unwrap();
+        let var = book.sp(a, 0);
+        let target = book.start(a, &[(Var('u'), "A".parse().unwrap())]).unwrap();
+        let j = book.inst_weak(target, &[var, var], 0).unwrap();
+        assert_eq!(book.judgements[j].m, "implies[(A),(A)]".parse().unwrap());
+        assert_eq!(book.judgements[j].n, Expr::Asterisk);
+        assert_eq!(book.judgements[j].context, book.judgements[target].context);
+    }
+
+    #[test]
+    fn convertible() {
+        let (book, j) = implies();
+        let definitions = book.judgements[j].definitions;
+        assert!(book.convertible(
+            definitions,
+            &"implies[(A),(B)]".parse().unwrap(),
+            &"?x:(A).(%($y:(*).(y))(B))".parse().unwrap()
+        ));
+        assert!(!book.convertible(
+            definitions,
+            &"implies[(A),(B)]".parse().unwrap(),
+            &"implies[(B),(A)]".parse().unwrap()
+        ));
+    }
+}
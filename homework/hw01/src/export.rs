@@ -0,0 +1,170 @@
+//! Printing shared by the exporters.
+//!
+//! Each target describes its notation in a [`Syntax`] table; [`Syntax::expr`]
+//! prints expressions in it with as few parentheses as precedence allows.
+//! Exported books are wrapped in a namespace or module of the target, so
+//! definitions like `and` do not clash with its standard library.
+
+use std::fmt::Write;
+
+use crate::model::Expr;
+
+/// Precedence levels, loosest first.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub(crate) enum Level {
+    Binder,
+    Application,
+    Atom,
+}
+
+pub(crate) fn parenthesize(s: String, own: Level, level: Level) -> String {
+    if own < level { format!("({s})") } else { s }
+}
+
+/// Whether `name` is an identifier in every target: ASCII letters, digits
+/// and `_`, not starting with a digit.
+fn is_plain(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// How a target spells definition names.
+pub(crate) struct Identifiers {
+    pub keywords: &'static [&'static str],
+    /// Turns a name that is not plain or is a keyword into an identifier.
+    pub escape: fn(&str) -> String,
+}
+
+impl Identifiers {
+    pub fn sanitize(&self, name: &str) -> String {
+        if is_plain(name) && !self.keywords.contains(&name) {
+            name.to_string()
+        } else {
+            (self.escape)(name)
+        }
+    }
+}
+
+/// The notation of an export target.
+pub(crate) struct Syntax {
+    pub identifiers: Identifiers,
+    pub asterisk: &'static str,
+    pub square: &'static str,
+    /// Separates the domain and codomain of a non-dependent product.
+    pub arrow: &'static str,
+    /// A dependent product of a variable, its printed type and the body.
+    pub pi: fn(char, &str, &str) -> String,
+    pub lambda: fn(char, &str, &str) -> String,
+    /// The level binder types are printed at.
+    pub domain: Level,
+}
+
+impl Syntax {
+    pub fn sanitize(&self, name: &str) -> String {
+        self.identifiers.sanitize(name)
+    }
+
+    /// Prints `e` at `level`, naming definitions by `name`.
+    pub fn expr(&self, e: &Expr, level: Level, name: &dyn Fn(&str) -> String) -> String {
+        let (s, own) = match e {
+            Expr::Var(x) => (x.0.to_string(), Level::Atom),
+            Expr::Asterisk => (self.asterisk.to_string(), Level::Atom),
+            Expr::Square => (self.square.to_string(), Level::Atom),
+            Expr::Meta(_) | Expr::Hole(None) => ("_".to_string(), Level::Atom),
+            Expr::Hole(Some(hole)) => (format!("?{hole}"), Level::Atom),
+            Expr::Definition(d) if d.1.is_empty() => (name(&d.0), Level::Atom),
+            Expr::Definition(d) => {
+                let mut s = name(&d.0);
+                for arg in &d.1 {
+                    write!(s, " {}", self.expr(arg, Level::Atom, name)).unwrap();
+                }
+                (s, Level::Application)
+            }
+            Expr::Application(a) => (
+                format!(
+                    "{} {}",
+                    self.expr(&a.0, Level::Application, name),
+                    self.expr(&a.1, Level::Atom, name)
+                ),
+                Level::Application,
+            ),
+            Expr::Pi(pi) if !pi.2.free_vars().contains(&pi.0) => (
+                format!(
+                    "{}{}{}",
+                    self.expr(&pi.1, Level::Application, name),
+                    self.arrow,
+                    self.expr(&pi.2, Level::Binder, name)
+                ),
+                Level::Binder,
+            ),
+            Expr::Pi(pi) => (
+                (self.pi)(
+                    pi.0.0,
+                    &self.expr(&pi.1, self.domain, name),
+                    &self.expr(&pi.2, Level::Binder, name),
+                ),
+                Level::Binder,
+            ),
+            Expr::Lambda(l) => (
+                (self.lambda)(
+                    l.0.0,
+                    &self.expr(&l.1, self.domain, name),
+                    &self.expr(&l.2, Level::Binder, name),
+                ),
+                Level::Binder,
+            ),
+        };
+        parenthesize(s, own, level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    const PLAIN: Syntax = Syntax {
+        identifiers: Identifiers {
+            keywords: &["fun"],
+            escape: |name| format!("<{name}>"),
+        },
+        asterisk: "*",
+        square: "@",
+        arrow: " -> ",
+        pi: |x, a, b| format!("all {x} : {a}, {b}"),
+        lambda: |x, a, b| format!("fn {x} : {a}, {b}"),
+        domain: Level::Application,
+    };
+
+    #[rstest]
+    #[case("and", "and")]
+    #[case("implies_in", "implies_in")]
+    #[case("fun", "<fun>")]
+    #[case("1st", "<1st>")]
+    #[case("a-b", "<a-b>")]
+    fn sanitize_names(#[case] name: &str, #[case] expected: &str) {
+        assert_eq!(PLAIN.sanitize(name), expected);
+    }
+
+    #[rstest]
+    #[case("?x:(*).(x)", "all x : *, x")]
+    #[case("?x:(A).(B)", "A -> B")]
+    #[case("?x:(A).(?y:(B).(C))", "A -> B -> C")]
+    #[case("?x:(?y:(A).(B)).(C)", "(A -> B) -> C")]
+    #[case("?x:(?y:(*).(y)).(x)", "all x : (all y : *, y), x")]
+    #[case("$x:(A).(%(f)(x))", "fn x : A, f x")]
+    #[case("%(%(f)(a))(%(g)(b))", "f a (g b)")]
+    #[case("%(f)($x:(A).(x))", "f (fn x : A, x)")]
+    #[case("implies[(A),(implies[(B),(C)])]", "implies A (implies B C)")]
+    #[case("contra[]", "contra")]
+    #[case("?x:(*).(@)", "* -> @")]
+    fn expressions(#[case] e: Expr, #[case] expected: &str) {
+        assert_eq!(
+            PLAIN.expr(&e, Level::Binder, &|name| PLAIN.sanitize(name)),
+            expected
+        );
+    }
+}
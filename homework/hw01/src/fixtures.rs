@@ -0,0 +1,25 @@
+//! Shared test fixtures.
+
+use crate::{
+    book::Book,
+    model::{Expr, Var},
+    rule::Resolver,
+};
+
+/// The definitions of `check/def2` and a judgement `* : @` in the context
+/// of `declarations`, given as variables and parsed types.
+pub fn def2(declarations: &[(char, &str)]) -> (Resolver, usize) {
+    let book: Book = include_str!("../check/def2").parse().unwrap();
+    let (mut resolver, anchor) = book.check().unwrap();
+    let declarations = declarations
+        .iter()
+        .map(|&(x, a)| (Var(x), a.parse::<Expr>().unwrap()))
+        .collect::<Vec<_>>();
+    let anchor = resolver.start(anchor, &declarations).unwrap();
+    (resolver, anchor)
+}
+
+/// [`def2`] with two propositions `A` and `B`.
+pub fn propositions() -> (Resolver, usize) {
+    def2(&[('A', "*"), ('B', "*")])
+}
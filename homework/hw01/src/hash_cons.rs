@@ -0,0 +1,215 @@
+use std::{
+    collections::{HashMap, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    ops::Deref,
+    rc::{Rc, Weak},
+};
+
+/// A hash-consed, shared node.
+///
+/// Values are interned in a per-thread table on construction, so two `Hc`s
+/// holding structurally equal values always point to the same allocation.
+/// Equality and hashing therefore only look at the pointer, which keeps them
+/// O(1) regardless of the size of the subterm.
+pub struct Hc<T>(Rc<T>);
+
+/// An interning table. It only holds weak references, so a node is freed as
+/// soon as the last [`Hc`] pointing to it is dropped; dead entries are
+/// purged when their bucket is looked up and by a sweep whenever the table
+/// has doubled since the last one.
+pub struct Table<T> {
+    buckets: HashMap<u64, Vec<Weak<T>>>,
+    entries: usize,
+    sweep_at: usize,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Self {
+            buckets: HashMap::new(),
+            entries: 0,
+            sweep_at: 1024,
+        }
+    }
+}
+
+impl<T: Hash + Eq> Table<T> {
+    fn intern(&mut self, value: T) -> Rc<T> {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let bucket = self.buckets.entry(hasher.finish()).or_default();
+
+        let before = bucket.len();
+        let mut found = None;
+        bucket.retain(|weak| match weak.upgrade() {
+            Some(rc) => {
+                if found.is_none() && *rc == value {
+                    found = Some(rc);
+                }
+                true
+            }
+            None => false,
+        });
+        self.entries -= before - bucket.len();
+        if let Some(rc) = found {
+            return rc;
+        }
+
+        let rc = Rc::new(value);
+        bucket.push(Rc::downgrade(&rc));
+        self.entries += 1;
+        if self.entries >= self.sweep_at {
+            self.sweep();
+        }
+        rc
+    }
+
+    /// Drops all dead entries.
+    fn sweep(&mut self) {
+        self.buckets.retain(|_, bucket| {
+            bucket.retain(|weak| weak.strong_count() > 0);
+            !bucket.is_empty()
+        });
+        self.entries = self.buckets.values().map(Vec::len).sum();
+        self.sweep_at = (2 * self.entries).max(1024);
+    }
+
+    fn live(&self) -> usize {
+        self.buckets
+            .values()
+            .flatten()
+            .filter(|weak| weak.strong_count() > 0)
+            .count()
+    }
+}
+
+/// Types that own an interning table for [`Hc`].
+pub trait Intern: Hash + Eq + Sized + 'static {
+    fn with_table<R>(f: impl FnOnce(&mut Table<Self>) -> R) -> R;
+}
+
+macro_rules! intern {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl $crate::hash_cons::Intern for $t {
+                fn with_table<R>(f: impl FnOnce(&mut $crate::hash_cons::Table<Self>) -> R) -> R {
+                    thread_local! {
+                        static TABLE: std::cell::RefCell<$crate::hash_cons::Table<$t>> =
+                            std::cell::RefCell::new(Default::default());
+                    }
+                    TABLE.with(|t| f(&mut t.borrow_mut()))
+                }
+            }
+        )*
+    };
+}
+pub(crate) use intern;
+
+impl<T: Intern> Hc<T> {
+    pub fn new(value: T) -> Self {
+        Self(T::with_table(|table| table.intern(value)))
+    }
+
+    /// Number of distinct values currently alive for `T` on this thread.
+    pub fn interned() -> usize {
+        T::with_table(|table| table.live())
+    }
+}
+
+impl<T> Hc<T> {
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Rc::ptr_eq(&a.0, &b.0)
+    }
+}
+
+impl<T> Clone for Hc<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Deref for Hc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> PartialEq for Hc<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T> Eq for Hc<T> {}
+
+impl<T> Hash for Hc<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).hash(state)
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Hc<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for Hc<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{Expr, Lambda, Var};
+
+    use super::*;
+
+    #[test]
+    fn identical_terms_share_nodes() {
+        let a: Expr = "$x:(*).(%(x)(implies[(x),(x)]))".parse().unwrap();
+        let b: Expr = "$x:(*).(%(x)(implies[(x),(x)]))".parse().unwrap();
+        let (Expr::Lambda(a), Expr::Lambda(b)) = (a, b) else {
+            panic!("expected lambdas");
+        };
+        assert!(Hc::ptr_eq(&a, &b));
+    }
+
+    #[test]
+    fn distinct_terms_do_not_share_nodes() {
+        let a: Expr = "$x:(*).(x)".parse().unwrap();
+        let b: Expr = "$y:(*).(y)".parse().unwrap();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn rebuilding_does_not_allocate() {
+        let body: Expr = "%(f)(x)".parse().unwrap();
+        let before = Hc::<Lambda>::interned();
+        let a = Expr::from(Lambda(Var('x'), Expr::Asterisk, body.clone()));
+        let b = Expr::from(Lambda(Var('x'), Expr::Asterisk, body));
+        assert_eq!(a, b);
+        assert_eq!(Hc::<Lambda>::interned(), before + 1);
+    }
+
+    #[test]
+    fn dropped_terms_are_freed() {
+        let before = Hc::<Lambda>::interned();
+        let e: Expr = "$x:(*).($y:(x).(%(y)(z)))".parse().unwrap();
+        assert_eq!(Hc::<Lambda>::interned(), before + 2);
+        drop(e);
+        assert_eq!(Hc::<Lambda>::interned(), before);
+    }
+
+    #[test]
+    fn table_is_swept() {
+        for i in 0..10_000 {
+            let _ = Expr::from(Lambda(Var('x'), Expr::Asterisk, Expr::Meta(i)));
+        }
+        let entries = Lambda::with_table(|table| table.entries);
+        assert!(entries < 2048, "{entries} entries after sweeping");
+    }
+}
@@ -0,0 +1,313 @@
+//! Typed holes.
+//!
+//! An expression may contain holes `_` and `?name`. Instead of failing on
+//! them, [`Resolver::holes`] type-checks the rest of the expression and
+//! reports every hole with its local context and, where it is known, the type
+//! expected at that position. Subterms and contexts without holes are checked
+//! by [`Resolver::derive`].
+
+use std::fmt::Display;
+
+use crate::{
+    model::{Expr, Pi, Var, generate_free_var_gte},
+    rule::Resolver,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Goal {
+    pub name: Option<String>,
+    pub context: Vec<(Var, Expr)>,
+    /// `None` if nothing determines the type of the hole.
+    pub ty: Option<Expr>,
+}
+
+/// Renders the goal as a judgement `x : A, y : B |- ?name : T`.
+impl Display for Goal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let context = self
+            .context
+            .iter()
+            .map(|(x, a)| format!("{} : {}", x.0, a.plain()))
+            .collect::<Vec<_>>();
+        write!(f, "{} |- ", context.join(", "))?;
+        write!(f, "{}", Expr::Hole(self.name.clone()).plain())?;
+        match &self.ty {
+            Some(ty) => write!(f, " : {}", ty.plain()),
+            None => write!(f, " : _"),
+        }
+    }
+}
+
+/// The local context of a subterm. `context` indexes the same declarations
+/// in [`Resolver::context`] as long as none of them contains a hole.
+#[derive(Clone)]
+struct Scope {
+    declarations: Vec<(Var, Expr)>,
+    context: Option<usize>,
+}
+
+struct Check<'r> {
+    resolver: &'r mut Resolver,
+    definitions: usize,
+    goals: Vec<Goal>,
+}
+
+impl Check<'_> {
+    fn lookup(scope: &Scope, x: Var) -> Result<Expr> {
+        scope
+            .declarations
+            .iter()
+            .rev()
+            .find(|(v, _)| *v == x)
+            .map(|(_, a)| a.clone())
+            .ok_or_else(|| format!("{x} is not declared in the context"))
+    }
+
+    /// Extends `scope` with `x : a`, renaming `x` in `body` if it is already
+    /// declared.
+    fn extend(
+        &mut self,
+        scope: &Scope,
+        x: Var,
+        a: &Expr,
+        body: &Expr,
+    ) -> Result<(Scope, Var, Expr)> {
+        let declared = scope.declarations.iter().any(|(v, _)| *v == x);
+        let (x, body) = if declared {
+            let mut avoid = body.free_vars();
+            avoid.extend(scope.declarations.iter().map(|(v, _)| *v));
+            let y = generate_free_var_gte(&avoid, x)?;
+            (y, body.alpha_substitution(x, y.into())?)
+        } else {
+            (x, body.clone())
+        };
+
+        let mut declarations = scope.declarations.clone();
+        declarations.push((x, a.clone()));
+        let context = match scope.context {
+            Some(c) if !a.has_holes() => Some(self.resolver.context.create(c, (x, a.clone()))),
+            _ => None,
+        };
+        Ok((
+            Scope {
+                declarations,
+                context,
+            },
+            x,
+            body,
+        ))
+    }
+
+    fn expect(&self, e: &Expr, ty: Option<Expr>, expected: Option<&Expr>) -> Result<Option<Expr>> {
+        match (&ty, expected) {
+            (Some(ty), Some(expected)) if ty.has_holes() || expected.has_holes() => {
+                Ok(Some(if ty.has_holes() { expected } else { ty }.clone()))
+            }
+            (Some(ty), Some(expected))
+                if !self.resolver.convertible(self.definitions, ty, expected) =>
+            {
+                Err(format!("{e} has type {ty}, expected {expected}"))
+            }
+            (None, Some(expected)) => Ok(Some(expected.clone())),
+            _ => Ok(ty),
+        }
+    }
+
+    /// The type expected of a binder type: a hole standing for the whole
+    /// binder type is expected to be a proposition or a set, of type `*`.
+    fn binder_sort(a: &Expr) -> Option<&Expr> {
+        matches!(a, Expr::Hole(_)).then_some(&Expr::Asterisk)
+    }
+
+    fn expect_sort(&self, e: &Expr, ty: &Option<Expr>) -> Result<()> {
+        match ty {
+            Some(ty) if !ty.is_sort() => {
+                Err(format!("{e} is not a type: its type {ty} is not a sort"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The type of `e` in `scope`, checked against `expected`. `None` if it
+    /// depends on a hole of unknown type.
+    fn infer(&mut self, scope: &Scope, e: &Expr, expected: Option<&Expr>) -> Result<Option<Expr>> {
+        if !e.has_holes()
+            && let Some(context) = scope.context
+        {
+            let anchor = self.resolver.anchor(self.definitions, context)?;
+            let j = self.resolver.derive(anchor, e)?;
+            let ty = self.resolver.judgements[j].n.clone();
+            return self.expect(e, Some(ty), expected);
+        }
+
+        let ty = match e {
+            Expr::Hole(name) => {
+                self.goals.push(Goal {
+                    name: name.clone(),
+                    context: scope.declarations.clone(),
+                    ty: expected.cloned(),
+                });
+                return Ok(expected.cloned());
+            }
+            Expr::Var(x) => Some(Self::lookup(scope, *x)?),
+            Expr::Asterisk => Some(Expr::Square),
+            Expr::Square => return Err(format!("{} has no type", Expr::Square)),
+            Expr::Meta(_) => return Err(format!("the metavariable {e} is unsolved")),
+            Expr::Pi(pi) => {
+                let ta = self.infer(scope, &pi.1, Self::binder_sort(&pi.1))?;
+                self.expect_sort(&pi.1, &ta)?;
+                let (inner, _, body) = self.extend(scope, pi.0, &pi.1, &pi.2)?;
+                let tb = self.infer(&inner, &body, None)?;
+                self.expect_sort(&body, &tb)?;
+                tb
+            }
+            Expr::Lambda(l) => {
+                let ta = self.infer(scope, &l.1, Self::binder_sort(&l.1))?;
+                self.expect_sort(&l.1, &ta)?;
+                let (inner, x, body) = self.extend(scope, l.0, &l.1, &l.2)?;
+                let expected = expected
+                    .map(|t| self.resolver.whnf(self.definitions, t))
+                    .transpose()?;
+                let codomain = match expected {
+                    Some(Expr::Pi(pi)) => Some(pi.2.alpha_substitution(pi.0, x.into())?),
+                    Some(t) if !t.has_holes() => {
+                        return Err(format!(
+                            "{e} is a function, but it is expected to have type {t}"
+                        ));
+                    }
+                    _ => None,
+                };
+                let tb = self.infer(&inner, &body, codomain.as_ref())?;
+                tb.map(|tb| Pi(x, l.1.clone(), tb).into())
+            }
+            Expr::Application(a) => {
+                let tf = self.infer(scope, &a.0, None)?;
+                match tf
+                    .map(|t| self.resolver.whnf(self.definitions, &t))
+                    .transpose()?
+                {
+                    Some(Expr::Pi(pi)) => {
+                        self.infer(scope, &a.1, Some(&pi.1))?;
+                        Some(pi.2.alpha_substitution(pi.0, a.1.clone())?)
+                    }
+                    Some(t) => {
+                        return Err(format!(
+                            "{} is applied to an argument, but its type {t} is not a product",
+                            a.0
+                        ));
+                    }
+                    None => {
+                        self.infer(scope, &a.1, None)?;
+                        None
+                    }
+                }
+            }
+            Expr::Definition(d) => {
+                let Some(entry) = self
+                    .resolver
+                    .definitions
+                    .resolve(self.definitions, |entry| entry.name == d.0)
+                else {
+                    return Err(format!("unknown definition {}", d.0));
+                };
+                let n = entry.n.clone();
+                let params = self.resolver.declarations(entry.context);
+                let args = match scope.context {
+                    Some(context) if d.1.len() < params.len() => {
+                        let anchor = self.resolver.anchor(self.definitions, context)?;
+                        self.resolver
+                            .implicit_arguments(anchor, &d.0, &params, &d.1)?
+                    }
+                    _ => d.1.clone(),
+                };
+                if params.len() != args.len() {
+                    return Err(format!(
+                        "{} expects {} arguments, found {}",
+                        d.0,
+                        params.len(),
+                        args.len()
+                    ));
+                }
+                let mut substitution = vec![];
+                for ((v, ty), u) in params.iter().zip(&args) {
+                    self.infer(scope, u, Some(&ty.substitute(&substitution)?))?;
+                    substitution.push((*v, u.clone()));
+                }
+                Some(n.substitute(&substitution)?)
+            }
+        };
+
+        self.expect(e, ty, expected)
+    }
+}
+
+impl Resolver {
+    /// Checks `e`, which may contain holes, in the environment and context of
+    /// the judgement `j`, optionally against the type `expected`. Returns the
+    /// holes in the order they occur.
+    pub fn holes(&mut self, j: usize, e: &Expr, expected: Option<&Expr>) -> Result<Vec<Goal>> {
+        let judgement = &self.judgements[j];
+        let (definitions, context) = (judgement.definitions, judgement.context);
+        let scope = Scope {
+            declarations: self.declarations(context),
+            context: Some(context),
+        };
+
+        let mut check = Check {
+            resolver: self,
+            definitions,
+            goals: vec![],
+        };
+        check.infer(&scope, e, expected)?;
+        Ok(check.goals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::fixtures::propositions;
+
+    #[rstest]
+    #[case("$x:(A).(_)", "?x:(A).(A)", &["A : *, B : *, x : A |- _ : A"])]
+    #[case(
+        "$f:(implies[(A),(B)]).($x:(A).(%(f)(?arg)))",
+        "implies[(implies[(A),(B)]),(implies[(A),(B)])]",
+        &["A : *, B : *, f : implies[(A),(B)], x : A |- ?arg : A"]
+    )]
+    #[case(
+        "and_in[(A),(B),(?a),(?b)]",
+        "and[(A),(B)]",
+        &["A : *, B : * |- ?a : A", "A : *, B : * |- ?b : B"]
+    )]
+    #[case("$x:(?t).(x)", "?x:(A).(A)", &["A : *, B : * |- ?t : *"])]
+    #[case("?x:(_).(A)", "*", &["A : *, B : * |- _ : *"])]
+    #[case(
+        "$a:(A).(and_in[(B),(a),(?b)])",
+        "?a:(A).(and[(A),(B)])",
+        &["A : *, B : *, a : A |- ?b : B"]
+    )]
+    #[case("%(_)(A)", "B", &["A : *, B : * |- _ : _"])]
+    fn goals(#[case] e: Expr, #[case] ty: Expr, #[case] expected: &[&str]) {
+        let (mut resolver, j) = propositions();
+        let goals = resolver.holes(j, &e, Some(&ty)).unwrap();
+        let goals = goals.iter().map(|g| g.to_string()).collect::<Vec<_>>();
+        assert_eq!(goals, expected);
+    }
+
+    #[rstest]
+    #[case("$x:(A).(_)", "B", "is a function")]
+    #[case("$x:(A).(x)", "?y:(A).(B)", "expected")]
+    #[case("%(A)(_)", "B", "is not a product")]
+    #[case("$x:(C).(_)", "A", "is not declared")]
+    #[case("and_in[(?a),(?b)]", "and[(A),(B)]", "cannot infer the argument for A")]
+    fn errors(#[case] e: Expr, #[case] ty: Expr, #[case] message: &str) {
+        let (mut resolver, j) = propositions();
+        let error = resolver.holes(j, &e, Some(&ty)).unwrap_err();
+        assert!(error.contains(message), "{error}");
+    }
+}
@@ -7,19 +7,27 @@ pub struct Entry<T> {
     pub value: T,
 }
 
+/// Values stored in an [`InTree`] are deduplicated by their key, so that
+/// e.g. context entries whose types are alpha-equivalent share an index.
+pub trait Keyed {
+    type Key: Hash + Eq;
+
+    fn key(&self) -> Self::Key;
+}
+
 #[derive(Debug)]
-pub struct InTree<T> {
-    pub lookup: HashMap<Entry<T>, usize>,
+pub struct InTree<T: Keyed> {
+    pub lookup: HashMap<Entry<T::Key>, usize>,
     pub entries: Vec<Option<Entry<T>>>,
 }
 
-impl<T> Default for InTree<T> {
+impl<T: Keyed> Default for InTree<T> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<T> InTree<T> {
+impl<T: Keyed> InTree<T> {
     pub fn new() -> Self {
         Self {
             lookup: HashMap::new(),
@@ -27,26 +35,27 @@ impl<T> InTree<T> {
         }
     }
 
-    pub fn create(&mut self, parent_index: usize, value: T) -> usize
-    where
-        T: Clone + Hash + Eq,
-    {
+    pub fn create(&mut self, parent_index: usize, value: T) -> usize {
         let len = match &self.entries[parent_index] {
             Some(p) => p.len + 1,
             None => 1,
         };
-        let e = Entry {
+        let key = Entry {
             parent_index,
-            value,
+            value: value.key(),
             len,
         };
 
-        if let Some(i) = self.lookup.get(&e) {
+        if let Some(i) = self.lookup.get(&key) {
             *i
         } else {
             let i = self.entries.len();
-            self.lookup.insert(e.clone(), i);
-            self.entries.push(Some(e.clone()));
+            self.lookup.insert(key, i);
+            self.entries.push(Some(Entry {
+                parent_index,
+                value,
+                len,
+            }));
             i
         }
     }
@@ -81,6 +90,20 @@ impl<T> InTree<T> {
         }
     }
 
+    /// Values along the path to `index`, starting at the root.
+    pub fn path(&self, index: usize) -> Vec<&T> {
+        let mut values = vec![];
+        let mut current = &self.entries[index];
+
+        while let Some(entry) = current {
+            values.push(&entry.value);
+            current = &self.entries[entry.parent_index];
+        }
+
+        values.reverse();
+        values
+    }
+
     pub fn traverse(&self, index: usize, nth_in_path: usize) -> Option<&Entry<T>> {
         let mut current = self.entries[index].as_ref()?;
 
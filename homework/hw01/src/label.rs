@@ -0,0 +1,210 @@
+//! Labelled derivation logs.
+//!
+//! The labelled format drops the line numbers of the numeric format accepted
+//! by [`crate::verifier::Verifier::run_line`]. A line may start with a label,
+//! e.g. `impl_form: form 9 12`, and premises may refer to earlier lines
+//! either by label or by line number. Blank lines and lines starting with `#`
+//! are ignored, and the log ends at the end of input or at `-1`.
+
+use std::collections::HashMap;
+
+type Result<T> = std::result::Result<T, String>;
+
+/// Positions of the operands of `instruction` that refer to judgements.
+fn judgement_operands(instruction: &str, operands: &[&str]) -> Result<Vec<usize>> {
+    let count = |i: usize| -> Result<usize> {
+        operands
+            .get(i)
+            .ok_or_else(|| format!("{instruction}: missing operand"))?
+            .parse()
+            .map_err(|_| format!("{instruction}: expected a count as operand {i}"))
+    };
+
+    Ok(match instruction {
+        "sort" => vec![],
+        "var" | "cp" | "sp" | "start" | "derive" => vec![0],
+        "weak" | "form" | "appl" | "abst" | "conv" | "def" | "defpr" | "weaken" => vec![0, 1],
+        "inst" | "instw" => {
+            let n = count(1)?;
+            std::iter::once(0).chain(2..2 + n).collect()
+        }
+        i => return Err(format!("unknown instruction {i}")),
+    })
+}
+
+fn is_label(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Converts a labelled log into the numeric format, terminated by `-1`.
+pub fn to_numeric(input: &str) -> Result<String> {
+    let mut labels = HashMap::new();
+    let mut output = String::new();
+    let mut index = 0;
+
+    for (lineno, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line == "-1" {
+            break;
+        }
+        let error = |e: String| format!("line {}: {e}", lineno + 1);
+
+        let mut tokens = line.split_whitespace().collect::<Vec<_>>();
+        if let Some(label) = tokens[0].strip_suffix(':') {
+            if !is_label(label) {
+                return Err(error(format!("invalid label {label:?}")));
+            }
+            if labels.insert(label.to_string(), index).is_some() {
+                return Err(error(format!("duplicate label {label}")));
+            }
+            tokens.remove(0);
+        }
+        let Some((instruction, operands)) = tokens.split_first() else {
+            return Err(error("missing instruction".to_string()));
+        };
+
+        let mut operands = operands.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let refs = judgement_operands(instruction, &tokens[1..]).map_err(error)?;
+        for i in refs {
+            let operand = operands
+                .get_mut(i)
+                .ok_or_else(|| error(format!("{instruction}: missing operand")))?;
+            let target = match operand.parse::<usize>() {
+                Ok(n) => n,
+                Err(_) => *labels
+                    .get(operand.as_str())
+                    .ok_or_else(|| error(format!("unknown label {operand}")))?,
+            };
+            if target >= index {
+                return Err(error(format!(
+                    "{operand} does not refer to an earlier line"
+                )));
+            }
+            *operand = target.to_string();
+        }
+
+        output.push_str(&index.to_string());
+        output.push(' ');
+        output.push_str(instruction);
+        for operand in operands {
+            output.push(' ');
+            output.push_str(&operand);
+        }
+        output.push('\n');
+        index += 1;
+    }
+
+    output.push_str("-1\n");
+    Ok(output)
+}
+
+/// Converts a numeric log into the labelled format. Every line that is
+/// referenced by a later one is labelled `l<line number>`.
+pub fn to_labelled(input: &str) -> Result<String> {
+    let mut lines = vec![];
+
+    for line in input.lines() {
+        if line == "-1" {
+            break;
+        }
+        let tokens = line.split(' ').collect::<Vec<_>>();
+        let lineno = lines.len();
+        if tokens[0].parse() != Ok(lineno) {
+            return Err(format!("line {lineno}: wrong line number {}", tokens[0]));
+        }
+        let Some(instruction) = tokens.get(1) else {
+            return Err(format!("line {lineno}: missing instruction"));
+        };
+        let refs = judgement_operands(instruction, &tokens[2..])
+            .map_err(|e| format!("line {lineno}: {e}"))?;
+        lines.push((tokens[1..].to_vec(), refs));
+    }
+
+    let mut referenced = vec![false; lines.len()];
+    for (tokens, refs) in &lines {
+        for i in refs {
+            let target: usize = tokens
+                .get(i + 1)
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| format!("{}: expected a line number", tokens[0]))?;
+            *referenced
+                .get_mut(target)
+                .ok_or_else(|| format!("reference to missing line {target}"))? = true;
+        }
+    }
+
+    let mut output = String::new();
+    for (lineno, (mut tokens, refs)) in lines.into_iter().enumerate() {
+        let labels = refs
+            .iter()
+            .map(|i| format!("l{}", tokens[i + 1]))
+            .collect::<Vec<_>>();
+        for (i, label) in refs.iter().zip(&labels) {
+            tokens[i + 1] = label.as_str();
+        }
+        if referenced[lineno] {
+            output.push_str(&format!("l{lineno}: "));
+        }
+        output.push_str(&tokens.join(" "));
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::verifier::Verifier;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let input = include_str!("../check/log");
+        let labelled = to_labelled(input).unwrap();
+        assert!(labelled.starts_with("l0: sort\nl1: cp l0\n"));
+        assert_eq!(to_numeric(&labelled).unwrap(), input);
+    }
+
+    #[test]
+    fn labelled_log() {
+        let input = "
+            # implies
+            sort: sort
+            ctx: start sort 2 A * B *
+            a: sp ctx 0
+            start ctx 1 a A
+            sp 3 1
+            form a 4
+            def sort 5 implies
+        ";
+        let numeric = to_numeric(input).unwrap();
+        assert_eq!(
+            numeric,
+            "0 sort\n1 start 0 2 A * B *\n2 sp 1 0\n3 start 1 1 a A\n4 sp 3 1\n5 form 2 4\n6 def 0 5 implies\n-1\n"
+        );
+        Verifier::run(&numeric);
+    }
+
+    #[test]
+    fn inst_operands() {
+        let numeric = to_numeric("s: sort\nc: cp s\nd: inst c 2 s c 0").unwrap();
+        assert!(numeric.ends_with("2 inst 1 2 0 1 0\n-1\n"));
+    }
+
+    #[rstest]
+    #[case("sort\ncp x", "unknown label x")]
+    #[case("a: sort\na: sort", "duplicate label a")]
+    #[case("sort\ncp 1", "does not refer to an earlier line")]
+    #[case("frob 0", "unknown instruction frob")]
+    fn errors(#[case] input: &str, #[case] message: &str) {
+        let error = to_numeric(input).unwrap_err();
+        assert!(error.contains(message), "{error}");
+    }
+}
@@ -0,0 +1,216 @@
+//! Export of books to Lean 4.
+//!
+//! `*` becomes `Prop` and `@` becomes `Type`, so propositions are mirrored as
+//! Lean propositions. Every block becomes a `def`, or an `axiom` if it is
+//! primitive, taking its parameters as explicit binders; instances of
+//! definitions become applications to all arguments. The output is wrapped
+//! in a namespace.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    book::Book,
+    export::{Identifiers, Level, Syntax},
+    model::Expr,
+};
+
+const KEYWORDS: &[&str] = &[
+    "abbrev",
+    "at",
+    "attribute",
+    "axiom",
+    "by",
+    "calc",
+    "class",
+    "def",
+    "deriving",
+    "do",
+    "else",
+    "end",
+    "example",
+    "exists",
+    "fun",
+    "forall",
+    "from",
+    "have",
+    "if",
+    "import",
+    "in",
+    "inductive",
+    "infix",
+    "instance",
+    "let",
+    "macro",
+    "match",
+    "mutual",
+    "namespace",
+    "noncomputable",
+    "notation",
+    "open",
+    "opaque",
+    "partial",
+    "private",
+    "Prop",
+    "protected",
+    "return",
+    "section",
+    "show",
+    "Sort",
+    "structure",
+    "syntax",
+    "then",
+    "theorem",
+    "Type",
+    "universe",
+    "variable",
+    "where",
+    "with",
+];
+
+/// Names that are not plain identifiers or clash with a keyword are quoted
+/// with `«»`.
+const LEAN: Syntax = Syntax {
+    identifiers: Identifiers {
+        keywords: KEYWORDS,
+        escape: |name| {
+            let quoted = name
+                .chars()
+                .map(|c| if c.is_control() || c == '»' { '_' } else { c })
+                .collect::<String>();
+            format!("«{quoted}»")
+        },
+    },
+    asterisk: "Prop",
+    square: "Type",
+    arrow: " → ",
+    pi: |x, a, b| format!("({x} : {a}) → {b}"),
+    lambda: |x, a, b| format!("fun ({x} : {a}) => {b}"),
+    domain: Level::Binder,
+};
+
+/// The Lean identifiers of the definitions of a book. `«a»` and `a` are the
+/// same identifier, and escaping maps several names to one, so clashing
+/// names get primes.
+struct Names(HashMap<String, String>);
+
+impl Names {
+    fn new(book: &Book) -> Self {
+        let mut names = HashMap::new();
+        let mut taken = vec![];
+        for block in &book.0 {
+            let mut name = LEAN.sanitize(&block.name);
+            while taken.contains(&unquoted(&name)) {
+                name = match name.strip_suffix('»') {
+                    Some(quoted) => format!("{quoted}'»"),
+                    None => format!("{name}'"),
+                };
+            }
+            taken.push(unquoted(&name));
+            names.insert(block.name.clone(), name);
+        }
+        Self(names)
+    }
+
+    fn get(&self, name: &str) -> String {
+        self.0
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| LEAN.sanitize(name))
+    }
+
+    fn expr(&self, e: &Expr, level: Level) -> String {
+        LEAN.expr(e, level, &|name| self.get(name))
+    }
+}
+
+fn unquoted(name: &str) -> String {
+    name.trim_start_matches('«')
+        .trim_end_matches('»')
+        .to_string()
+}
+
+impl Book {
+    /// The book as a Lean 4 source file, with all definitions in
+    /// `namespace`.
+    pub fn to_lean(&self, namespace: &str) -> String {
+        let names = Names::new(self);
+        let mut out = String::new();
+        writeln!(out, "namespace {}\n", LEAN.sanitize(namespace)).unwrap();
+        for block in &self.0 {
+            let keyword = if block.m.is_some() { "def" } else { "axiom" };
+            write!(out, "{keyword} {}", names.get(&block.name)).unwrap();
+            for (x, a) in &block.context {
+                write!(out, " ({} : {})", x.0, names.expr(a, Level::Binder)).unwrap();
+            }
+            write!(out, " : {}", names.expr(&block.n, Level::Binder)).unwrap();
+            match &block.m {
+                Some(m) => writeln!(out, " :=\n  {}\n", names.expr(m, Level::Binder)).unwrap(),
+                None => writeln!(out, "\n").unwrap(),
+            }
+        }
+        writeln!(out, "end {}", LEAN.sanitize(namespace)).unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("fun", "«fun»")]
+    #[case("a-b", "«a-b»")]
+    #[case("a»b", "«a_b»")]
+    fn sanitize_names(#[case] name: &str, #[case] expected: &str) {
+        assert_eq!(LEAN.sanitize(name), expected);
+    }
+
+    #[rstest]
+    #[case("?x:(*).(x)", "(x : Prop) → x")]
+    #[case("?x:(A).(B)", "A → B")]
+    #[case("?x:(?y:(*).(y)).(x)", "(x : (y : Prop) → y) → x")]
+    #[case("$x:(A).(%(f)(x))", "fun (x : A) => f x")]
+    #[case("?x:(*).(@)", "Prop → Type")]
+    fn expressions(#[case] e: Expr, #[case] expected: &str) {
+        let names = Names::new(&Book::default());
+        assert_eq!(names.expr(&e, Level::Binder), expected);
+    }
+
+    #[test]
+    fn export_axioms_and_keywords() {
+        let book: Book = "def2\n0\nfun\n#\n*\nedef2\n\
+                          def2\n1\nx\nfun[]\nlemma\n#\n*\nedef2\n\
+                          def2\n0\nsame\nfun[]\n*\nedef2\nEND"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            book.to_lean("T"),
+            "namespace T\n\n\
+             axiom «fun» : Prop\n\n\
+             axiom lemma (x : «fun») : Prop\n\n\
+             def same : Prop :=\n  «fun»\n\n\
+             end T\n"
+        );
+    }
+
+    #[test]
+    fn export_clashes() {
+        let book: Book = "def2\n0\na_b\n#\n*\nedef2\n\
+                          def2\n0\na»b\n#\n*\nedef2\n\
+                          def2\n0\na-b\na_b[]\n*\nedef2\n\
+                          def2\n0\na_b'\na_b[]\n*\nedef2\nEND"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            book.to_lean("T"),
+            "namespace T\n\n\
+             axiom a_b : Prop\n\n\
+             axiom «a_b'» : Prop\n\n\
+             def «a-b» : Prop :=\n  a_b\n\n\
+             def «a_b''» : Prop :=\n  a_b\n\n\
+             end T\n"
+        );
+    }
+}
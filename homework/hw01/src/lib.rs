@@ -1,6 +1,25 @@
+pub mod automath;
+pub mod book;
+pub mod church;
+pub mod coq;
 pub mod de_bruijn;
+pub mod dedukti;
+pub mod deps;
+pub mod derived;
+mod export;
+#[cfg(test)]
+mod fixtures;
+pub mod hash_cons;
+pub mod hole;
 pub mod in_tree;
+pub mod label;
+pub mod lean;
+pub mod library;
 pub mod model;
 pub mod parse;
+pub mod prove;
+pub mod repl;
 pub mod rule;
+pub mod tactic;
+pub mod unify;
 pub mod verifier;
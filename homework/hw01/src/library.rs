@@ -0,0 +1,125 @@
+//! A bundled library of checked def2 books.
+//!
+//! Each book only holds its own definitions; [`book`] prepends the books it
+//! depends on, so the result can be checked on its own.
+
+use crate::book::Book;
+
+/// The library books: name, dependencies and def2 source.
+const BOOKS: &[(&str, &[&str], &str)] = &[
+    ("logic", &[], include_str!("../library/logic.def2")),
+    (
+        "quantifiers",
+        &["logic"],
+        include_str!("../library/quantifiers.def2"),
+    ),
+    (
+        "equality",
+        &["logic"],
+        include_str!("../library/equality.def2"),
+    ),
+    (
+        "peano",
+        &["logic", "equality"],
+        include_str!("../library/peano.def2"),
+    ),
+    (
+        "classical",
+        &["logic"],
+        include_str!("../library/classical.def2"),
+    ),
+];
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    BOOKS.iter().map(|(name, _, _)| *name)
+}
+
+fn collect(name: &str, book: &mut Book, loaded: &mut Vec<&'static str>) -> Option<()> {
+    let (name, dependencies, source) = BOOKS.iter().find(|(n, _, _)| *n == name)?;
+    if loaded.contains(name) {
+        return Some(());
+    }
+    for dependency in *dependencies {
+        collect(dependency, book, loaded)?;
+    }
+    loaded.push(name);
+    let own: Book = source
+        .parse()
+        .unwrap_or_else(|e| panic!("library book {name}: {e}"));
+    book.0.extend(own.0);
+    Some(())
+}
+
+/// The library book `name` preceded by its dependencies.
+pub fn book(name: &str) -> Option<Book> {
+    let mut book = Book::default();
+    collect(name, &mut book, &mut vec![])?;
+    Some(book)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::verifier::Verifier;
+
+    #[test]
+    fn books_check() {
+        for name in names() {
+            let book = book(name).unwrap();
+            if let Err(e) = book.check() {
+                panic!("{name}: {e}");
+            }
+        }
+    }
+
+    #[test]
+    fn dependencies() {
+        let names = book("classical")
+            .unwrap()
+            .0
+            .into_iter()
+            .map(|b| b.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names.first().map(String::as_str), Some("implies"));
+        assert_eq!(names.last().map(String::as_str), Some("contrapositive_rev"));
+        assert!(book("missing").is_none());
+    }
+
+    #[test]
+    fn peano_log() {
+        let (resolver, anchor) = book("peano").unwrap().check().unwrap();
+        let log = resolver.log();
+        assert_eq!(log, include_str!("../check/peano_log"));
+
+        let replayed = Verifier::run(&log).into_resolver();
+        let definitions = replayed.judgements.last().unwrap().definitions;
+        assert_eq!(
+            replayed.book(definitions),
+            resolver.book(resolver.judgements[anchor].definitions)
+        );
+    }
+
+    #[rstest]
+    #[case("logic", "and_comm", &[])]
+    #[case("logic", "iff_sym", &[])]
+    #[case("quantifiers", "not_exists_forall_not", &[])]
+    #[case("equality", "eq_cong", &[])]
+    #[case("classical", "excluded_middle", &["dne"])]
+    #[case("classical", "contrapositive_rev", &["dne"])]
+    #[case(
+        "peano",
+        "plus_comm",
+        &["induction", "nat", "plus", "plus_succ", "plus_zero", "succ", "zero"]
+    )]
+    fn axioms(#[case] library: &str, #[case] name: &str, #[case] expected: &[&str]) {
+        let (resolver, anchor) = book(library).unwrap().check().unwrap();
+        let definitions = resolver.judgements[anchor].definitions;
+        let axioms = resolver.axioms(definitions, name).unwrap();
+        assert_eq!(
+            axioms.iter().map(String::as_str).collect::<Vec<_>>(),
+            expected
+        );
+    }
+}
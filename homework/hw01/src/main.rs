@@ -1,8 +1,15 @@
-use std::io::{BufRead, BufReader};
+use std::{
+    io::{BufRead, BufReader},
+    path::Path,
+    process::ExitCode,
+};
 
-use hw01::parse::take_expr;
+use hw01::{
+    book::Book, deps::Graph, library, parse::take_expr, repl::Repl, rule::Resolver,
+    verifier::Verifier,
+};
 
-fn main() {
+fn parse_stdin() {
     let stdin = BufReader::new(std::io::stdin());
     for line in stdin.lines() {
         let line = line.unwrap();
@@ -22,3 +29,131 @@ fn main() {
         }
     }
 }
+
+/// Loads a def2 book, a derivation log or a library book, returning the resolver and the
+/// environment holding all of its definitions.
+fn load(path: &str) -> Result<(Resolver, usize), String> {
+    if !Path::new(path).exists()
+        && let Some(book) = library::book(path)
+    {
+        let (resolver, anchor) = book.check().map_err(|e| format!("{path}: {e}"))?;
+        let definitions = resolver.judgements[anchor].definitions;
+        return Ok((resolver, definitions));
+    }
+    let input = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+
+    if input.trim_start().starts_with("def2") {
+        let book: Book = input.parse().map_err(|e| format!("{path}: {e}"))?;
+        let (resolver, anchor) = book.check().map_err(|e| format!("{path}: {e}"))?;
+        let definitions = resolver.judgements[anchor].definitions;
+        Ok((resolver, definitions))
+    } else {
+        let resolver = Verifier::try_run(&input)
+            .map_err(|e| format!("{path}: {e}"))?
+            .into_resolver();
+        let definitions = resolver.judgements.last().map_or(0, |j| j.definitions);
+        Ok((resolver, definitions))
+    }
+}
+
+fn axioms(path: &str, name: &str) -> Result<(), String> {
+    let (resolver, definitions) = load(path)?;
+    let axioms = resolver
+        .axioms(definitions, name)
+        .ok_or_else(|| format!("unknown definition {name}"))?;
+
+    if axioms.is_empty() {
+        println!("{name} does not depend on any axioms");
+    } else {
+        println!("{name} depends on axioms:");
+        for axiom in axioms {
+            println!("  {axiom}");
+        }
+    }
+    Ok(())
+}
+
+fn graph(path: &str) -> Result<(), String> {
+    let input = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let book: Book = input.parse().map_err(|e| format!("{path}: {e}"))?;
+    print!("{}", Graph::new(&book).dot());
+    Ok(())
+}
+
+fn lean(path: &str, namespace: &str) -> Result<(), String> {
+    let (resolver, definitions) = load(path)?;
+    print!("{}", resolver.book(definitions).to_lean(namespace));
+    Ok(())
+}
+
+fn coq(path: &str, module: &str) -> Result<(), String> {
+    let (resolver, definitions) = load(path)?;
+    print!("{}", resolver.book(definitions).to_coq(module));
+    Ok(())
+}
+
+fn dedukti(path: &str) -> Result<(), String> {
+    let (mut resolver, definitions) = load(path)?;
+    print!("{}", resolver.to_dedukti(definitions)?);
+    Ok(())
+}
+
+fn from_dedukti(path: &str) -> Result<(), String> {
+    let input = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let book = Book::from_dedukti(&input).map_err(|e| format!("{path}: {e}"))?;
+    print!("{book}");
+    Ok(())
+}
+
+fn from_automath(path: &str) -> Result<(), String> {
+    let input = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let book = Book::from_automath(&input).map_err(|e| format!("{path}: {e}"))?;
+    print!("{book}");
+    Ok(())
+}
+
+fn log(path: &str) -> Result<(), String> {
+    let (resolver, _) = load(path)?;
+    print!("{}", resolver.log());
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let result = match args.as_slice() {
+        [] => {
+            parse_stdin();
+            Ok(())
+        }
+        ["axioms", path, name] => axioms(path, name),
+        ["graph", path] => graph(path),
+        ["log", path] => log(path),
+        ["coq", path] => coq(path, "Book"),
+        ["coq", path, module] => coq(path, module),
+        ["dedukti", path] => dedukti(path),
+        ["from-dedukti", path] => from_dedukti(path),
+        ["from-automath", path] => from_automath(path),
+        ["lean", path] => lean(path, "Book"),
+        ["lean", path, namespace] => lean(path, namespace),
+        ["repl"] => Repl::new()
+            .run(std::io::stdin().lock(), std::io::stdout())
+            .map_err(|e| e.to_string()),
+        _ => Err(
+            "usage: hw01 [axioms <book or log> <name> | graph <book> | log <book or log> \
+                    | lean <book or log> [<namespace>] \
+                    | coq <book or log> [<module>] | dedukti <book or log> \
+                    | from-dedukti <file> | from-automath <file> | repl]"
+                .to_string(),
+        ),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use colored::Colorize;
 use std::{collections::HashSet, fmt::Display, str::FromStr};
 
+use crate::hash_cons::{Hc, intern};
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Var(pub char);
 
@@ -85,15 +87,25 @@ impl Display for Application {
     }
 }
 
+intern!(Lambda, Pi, Definition, Application);
+
+/// Expressions are hash-consed: compound nodes are shared through [`Hc`], so
+/// cloning is cheap and identical subterms compare equal in O(1).
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Expr {
     Var(Var),
     Asterisk,
     Square,
-    Lambda(Box<Lambda>),
-    Pi(Box<Pi>),
-    Definition(Definition),
-    Application(Box<Application>),
+    Lambda(Hc<Lambda>),
+    Pi(Hc<Pi>),
+    Definition(Hc<Definition>),
+    Application(Hc<Application>),
+    /// A metavariable, standing for an expression to be inferred. Written
+    /// `_n`, it cannot be parsed.
+    Meta(usize),
+    /// A hole `_` or `?name` left for the user to fill in, see
+    /// [`crate::hole`].
+    Hole(Option<String>),
 }
 
 impl Display for Expr {
@@ -106,6 +118,38 @@ impl Display for Expr {
             Expr::Pi(pi) => write!(f, "{pi}"),
             Expr::Definition(definition) => write!(f, "{definition}"),
             Expr::Application(application) => write!(f, "{application}"),
+            Expr::Meta(m) => write!(f, "{}", format!("_{m}").yellow()),
+            Expr::Hole(_) => write!(f, "{}", Plain(self).to_string().yellow()),
+        }
+    }
+}
+
+/// Displays an expression in the plain input syntax, without colors, e.g.
+/// for writing files.
+pub struct Plain<'a>(pub &'a Expr);
+
+impl Display for Plain<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Expr::Var(var) => write!(f, "{}", var.0),
+            Expr::Asterisk => write!(f, "*"),
+            Expr::Square => write!(f, "@"),
+            Expr::Lambda(l) => write!(f, "${}:({}).({})", l.0.0, Plain(&l.1), Plain(&l.2)),
+            Expr::Pi(pi) => write!(f, "?{}:({}).({})", pi.0.0, Plain(&pi.1), Plain(&pi.2)),
+            Expr::Definition(d) => {
+                write!(f, "{}[", d.0)?;
+                for (i, x) in d.1.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "({})", Plain(x))?;
+                }
+                write!(f, "]")
+            }
+            Expr::Application(a) => write!(f, "%({})({})", Plain(&a.0), Plain(&a.1)),
+            Expr::Meta(m) => write!(f, "_{m}"),
+            Expr::Hole(None) => write!(f, "_"),
+            Expr::Hole(Some(name)) => write!(f, "?{name}"),
         }
     }
 }
@@ -130,25 +174,25 @@ impl From<Square> for Expr {
 
 impl From<Lambda> for Expr {
     fn from(value: Lambda) -> Self {
-        Self::Lambda(Box::new(value))
+        Self::Lambda(Hc::new(value))
     }
 }
 
 impl From<Pi> for Expr {
     fn from(value: Pi) -> Self {
-        Expr::Pi(Box::new(value))
+        Expr::Pi(Hc::new(value))
     }
 }
 
 impl From<Definition> for Expr {
     fn from(value: Definition) -> Self {
-        Expr::Definition(value)
+        Expr::Definition(Hc::new(value))
     }
 }
 
 impl From<Application> for Expr {
     fn from(value: Application) -> Self {
-        Expr::Application(Box::new(value))
+        Expr::Application(Hc::new(value))
     }
 }
 
@@ -162,6 +206,10 @@ impl FromStr for Expr {
 }
 
 impl Expr {
+    pub fn plain(&self) -> Plain<'_> {
+        Plain(self)
+    }
+
     pub fn is_sort(&self) -> bool {
         matches!(self, Self::Asterisk | Self::Square)
     }
@@ -170,14 +218,59 @@ impl Expr {
         crate::de_bruijn::de_bruijn(self, &crate::de_bruijn::Bindings::new(None))
     }
 
-    pub fn alpha_substitution(&self, var: Var, expr: Expr) -> Expr {
-        crate::de_bruijn::de_bruijn(self, &crate::de_bruijn::Bindings::new(Some((var, expr))))
-            .into()
+    /// Equality up to renaming of bound variables.
+    pub fn alpha_eq(&self, other: &Expr) -> bool {
+        self == other || self.de_bruijn() == other.de_bruijn()
+    }
+
+    pub fn alpha_substitution(&self, var: Var, expr: Expr) -> Result<Expr, String> {
+        self.substitute(&[(var, expr)])
+    }
+
+    /// Capture-avoiding simultaneous substitution of free variables. Fails
+    /// when no name is left to rename a binder.
+    pub fn substitute(&self, substitution: &[(Var, Expr)]) -> Result<Expr, String> {
+        let fv = self.free_vars();
+        if substitution.iter().all(|(v, _)| !fv.contains(v)) {
+            return Ok(self.clone());
+        }
+        let substitution = substitution
+            .iter()
+            .map(|(v, e)| (*v, e.de_bruijn()))
+            .collect::<Vec<_>>();
+        self.de_bruijn().substitute(&substitution).try_into()
+    }
+
+    /// Beta normal form.
+    pub fn normalize(&self) -> Result<Expr, String> {
+        self.de_bruijn().normalize().try_into()
+    }
+
+    pub fn has_metas(&self) -> bool {
+        match self {
+            Expr::Meta(_) => true,
+            Expr::Var(_) | Expr::Asterisk | Expr::Square | Expr::Hole(_) => false,
+            Expr::Lambda(l) => l.1.has_metas() || l.2.has_metas(),
+            Expr::Pi(pi) => pi.1.has_metas() || pi.2.has_metas(),
+            Expr::Definition(d) => d.1.iter().any(Expr::has_metas),
+            Expr::Application(a) => a.0.has_metas() || a.1.has_metas(),
+        }
+    }
+
+    pub fn has_holes(&self) -> bool {
+        match self {
+            Expr::Hole(_) => true,
+            Expr::Var(_) | Expr::Asterisk | Expr::Square | Expr::Meta(_) => false,
+            Expr::Lambda(l) => l.1.has_holes() || l.2.has_holes(),
+            Expr::Pi(pi) => pi.1.has_holes() || pi.2.has_holes(),
+            Expr::Definition(d) => d.1.iter().any(Expr::has_holes),
+            Expr::Application(a) => a.0.has_holes() || a.1.has_holes(),
+        }
     }
 
     pub fn free_vars(&self) -> HashSet<Var> {
         match self {
-            Expr::Asterisk | Expr::Square => HashSet::new(),
+            Expr::Asterisk | Expr::Square | Expr::Meta(_) | Expr::Hole(_) => HashSet::new(),
             Expr::Var(var) => HashSet::from([*var]),
             Expr::Lambda(lambda) => {
                 let mut fv = lambda.2.free_vars();
@@ -206,12 +299,23 @@ impl Expr {
     }
 }
 
-pub fn generate_free_var_gte(fv: &HashSet<Var>, mut v: Var) -> Var {
-    #[allow(clippy::char_lit_as_u8)]
-    while fv.contains(&v) {
-        v.0 = ((v.0 as u8 + 1) % ('a' as u8)) as char;
+/// Returns `v` or the first variable after it, cycling through `a..z` and
+/// then `A..Z`, that is not in `fv`. Fails once all 52 names are taken.
+pub fn generate_free_var_gte(fv: &HashSet<Var>, mut v: Var) -> Result<Var, String> {
+    for _ in 0..=52 {
+        if !fv.contains(&v) {
+            return Ok(v);
+        }
+        v.0 = match v.0 {
+            'z' => 'A',
+            'Z' => 'a',
+            c if c.is_ascii_alphabetic() => (c as u8 + 1) as char,
+            _ => 'a',
+        };
     }
-    v
+    Err(format!(
+        "no variable name is left to rename {v}: all 52 letters are in use"
+    ))
 }
 
 #[cfg(test)]
@@ -231,4 +335,47 @@ mod tests {
             fv.into_iter().map(Var).collect::<HashSet<Var>>()
         );
     }
+
+    #[rstest]
+    #[case("x")]
+    #[case("*")]
+    #[case("@")]
+    #[case("$x:(*).(%(x)(y))")]
+    #[case("?x:(A).(implies[(x),(contra[])])")]
+    fn plain(#[case] input: &str) {
+        let e: Expr = input.parse().unwrap();
+        assert_eq!(e.plain().to_string(), input);
+    }
+
+    #[rstest]
+    #[case(['x'], 'x', 'y')]
+    #[case(['z'], 'z', 'A')]
+    #[case(['Z', 'a'], 'Z', 'b')]
+    fn generate_free_var(
+        #[case] fv: impl IntoIterator<Item = char>,
+        #[case] v: char,
+        #[case] expected: char,
+    ) {
+        let fv = fv.into_iter().map(Var).collect();
+        assert_eq!(generate_free_var_gte(&fv, Var(v)), Ok(Var(expected)));
+    }
+
+    #[test]
+    fn generate_free_var_exhausted() {
+        let fv = ('a'..='z').chain('A'..='Z').map(Var).collect();
+        assert!(generate_free_var_gte(&fv, Var('x')).is_err());
+    }
+
+    #[rstest]
+    #[case("$x:(*).(x)", "$y:(*).(y)", true)]
+    #[case("?x:(A).(implies[(x),(B)])", "?z:(A).(implies[(z),(B)])", true)]
+    #[case("$x:(*).(x)", "$x:(*).(y)", false)]
+    #[case("?x:(A).(x)", "$x:(A).(x)", false)]
+    fn alpha_eq(#[case] a: Expr, #[case] b: Expr, #[case] eq: bool) {
+        assert_eq!(a.alpha_eq(&b), eq);
+        assert_eq!(
+            std::collections::HashMap::from([(a.de_bruijn(), ())]).contains_key(&b.de_bruijn()),
+            eq
+        );
+    }
 }
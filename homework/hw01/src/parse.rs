@@ -45,6 +45,23 @@ fn take_pi(input: &mut &[char]) -> Result<Pi> {
     Ok(Pi(x, m, n))
 }
 
+/// `?x:(A).(B)` is a product, while `?name` not followed by `:` is a hole.
+fn take_pi_or_hole(input: &mut &[char]) -> Result<Expr> {
+    let mut i = 0;
+    while i < input.len()
+        && (input[i].is_ascii_alphabetic()
+            || i > 0 && (input[i].is_ascii_digit() || input[i] == '_'))
+    {
+        i += 1;
+    }
+    if i == 0 || input.get(i) == Some(&':') {
+        return take_pi(input).map(Into::into);
+    }
+    let name = input[..i].iter().collect();
+    *input = &input[i..];
+    Ok(Expr::Hole(Some(name)))
+}
+
 fn take_lambda(input: &mut &[char]) -> Result<Lambda> {
     let x = take_var(input)?;
     take_exact(input, ':')?;
@@ -88,7 +105,10 @@ fn take_definition(name: String, input: &mut &[char]) -> Result<Definition> {
 
 pub fn take_expr(input: &mut &[char]) -> Result<Expr> {
     let mut i = 0;
-    while i < input.len() && input[i].is_ascii_alphabetic() {
+    while i < input.len()
+        && (input[i].is_ascii_alphabetic()
+            || i > 0 && (input[i].is_ascii_digit() || input[i] == '_'))
+    {
         i += 1;
     }
     if i == 1 {
@@ -109,7 +129,8 @@ pub fn take_expr(input: &mut &[char]) -> Result<Expr> {
         '@' => Ok(Expr::Square),
         '%' => take_application(input).map(Into::into),
         '$' => take_lambda(input).map(Into::into),
-        '?' => take_pi(input).map(Into::into),
+        '_' => Ok(Expr::Hole(None)),
+        '?' => take_pi_or_hole(input),
         _ => Err(format!("Unexpected: {:?}", c)),
     }
 }
@@ -133,7 +154,12 @@ mod tests {
     #[case(b"?x:(M).(N)")]
     #[case(b"empty[]")]
     #[case(b"implies[(M),(N)]")]
+    #[case(b"implies_in[(M),(N)]")]
+    #[case(b"a1_fig118[(A),(u),(v)]")]
     #[case(b"?x:(?x:(M).(%(a)(b))).(%(a)(b))")]
+    #[case(b"_")]
+    #[case(b"?goal")]
+    #[case(b"$x:(A).(%(?f)(_))")]
     fn valid(#[case] input: &'static [u8]) {
         let v = input.iter().map(|b| *b as char).collect::<Vec<_>>();
         println!("{:?}", take_expr(&mut v.as_slice()).unwrap());
@@ -0,0 +1,332 @@
+//! Proof search for intuitionistic propositional logic.
+//!
+//! Propositions are types of type `*`. Implication is a non-dependent
+//! product, and falsity, conjunction and disjunction are recognised in
+//! their impredicative encodings
+//!
+//! ```text
+//! ?c:(*).(c)
+//! ?c:(*).(?w:(?a:(A).(?b:(B).(c))).(c))
+//! ?c:(*).(?l:(?a:(A).(c)).(?r:(?b:(B).(c)).(c)))
+//! ```
+//!
+//! after unfolding definitions, so `implies`, `not`, `contra` and `and` from
+//! `check/def2` are understood. The search is LJ with a loop check: a
+//! sequent that already occurs on the current branch fails.
+
+use std::collections::HashSet;
+
+use crate::{
+    de_bruijn,
+    model::{Application, Expr, Lambda, Var, generate_free_var_gte},
+    rule::Resolver,
+};
+
+#[derive(Clone)]
+enum Shape {
+    Bottom,
+    And(Expr, Expr),
+    Or(Expr, Expr),
+    Implies(Expr, Expr),
+    Atom,
+}
+
+/// A hypothesis: a proof `term` of a proposition, together with the normal
+/// form and the shape of that proposition.
+#[derive(Clone)]
+struct Hyp {
+    term: Expr,
+    key: de_bruijn::Expr,
+    shape: Shape,
+}
+
+struct Search<'r> {
+    resolver: &'r Resolver,
+    definitions: usize,
+    /// Sequents on the current branch, as the number of hypotheses and the
+    /// goal. Hypotheses only grow along a branch, so their number identifies
+    /// them.
+    path: Vec<(usize, de_bruijn::Expr)>,
+}
+
+fn app(f: Expr, x: Expr) -> Expr {
+    Application(f, x).into()
+}
+
+fn lambda(x: Var, ty: Expr, body: Expr) -> Expr {
+    Lambda(x, ty, body).into()
+}
+
+impl Search<'_> {
+    /// Weak head normal form, or `None` once names run out, which ends the
+    /// branch.
+    fn whnf(&self, e: &Expr) -> Option<Expr> {
+        self.resolver.whnf(self.definitions, e).ok()
+    }
+
+    /// `A` and `B` if `e` unfolds to a non-dependent product `A -> B`.
+    fn arrow(&self, e: &Expr) -> Option<(Expr, Expr)> {
+        match self.whnf(e)? {
+            Expr::Pi(pi) if !pi.2.free_vars().contains(&pi.0) => Some((pi.1.clone(), pi.2.clone())),
+            _ => None,
+        }
+    }
+
+    /// Whether `e` unfolds to the variable `c`.
+    fn is_var(&self, e: &Expr, c: Var) -> bool {
+        self.whnf(e) == Some(Expr::Var(c))
+    }
+
+    fn shape(&self, e: &Expr) -> Shape {
+        let Some(Expr::Pi(pi)) = self.whnf(e) else {
+            return Shape::Atom;
+        };
+        let c = pi.0;
+        if !pi.2.free_vars().contains(&c) {
+            return Shape::Implies(pi.1.clone(), pi.2.clone());
+        }
+        if pi.1 != Expr::Asterisk {
+            return Shape::Atom;
+        }
+        if pi.2 == Expr::Var(c) {
+            return Shape::Bottom;
+        }
+
+        // Conjunction: (A -> B -> c) -> c.
+        let independent = |e: &Expr| !e.free_vars().contains(&c);
+        if let Some((premise, conclusion)) = self.arrow(&pi.2)
+            && self.is_var(&conclusion, c)
+            && let Some((a, rest)) = self.arrow(&premise)
+            && let Some((b, c2)) = self.arrow(&rest)
+            && self.is_var(&c2, c)
+            && independent(&a)
+            && independent(&b)
+        {
+            return Shape::And(a, b);
+        }
+        Shape::Atom
+    }
+
+    /// Recognises `(A -> c) -> (B -> c) -> c` under a binder `c : *`.
+    fn or(&self, e: &Expr) -> Option<(Expr, Expr)> {
+        let Expr::Pi(pi) = self.whnf(e)? else {
+            return None;
+        };
+        let c = pi.0;
+        if pi.1 != Expr::Asterisk {
+            return None;
+        }
+        let independent = |e: &Expr| !e.free_vars().contains(&c);
+        let (left, rest) = self.arrow(&pi.2)?;
+        let (right, conclusion) = self.arrow(&rest)?;
+        let (a, c1) = self.arrow(&left)?;
+        let (b, c2) = self.arrow(&right)?;
+        (self.is_var(&conclusion, c)
+            && self.is_var(&c1, c)
+            && self.is_var(&c2, c)
+            && independent(&a)
+            && independent(&b))
+        .then_some((a, b))
+    }
+
+    fn classify(&self, e: &Expr) -> Shape {
+        match self.or(e) {
+            Some((a, b)) => Shape::Or(a, b),
+            None => self.shape(e),
+        }
+    }
+
+    /// Beta-delta normal form, identifying convertible propositions.
+    fn key(&self, e: &Expr) -> de_bruijn::Expr {
+        e.de_bruijn()
+            .normalize_with(&|d| self.resolver.unfold(self.definitions, d))
+    }
+
+    fn find<'h>(&self, hyps: &'h [Hyp], ty: &Expr) -> Option<&'h Hyp> {
+        let key = self.key(ty);
+        hyps.iter().find(|h| h.key == key)
+    }
+
+    fn present(&self, hyps: &[Hyp], ty: &Expr) -> bool {
+        self.find(hyps, ty).is_some()
+    }
+
+    /// A variable not in `used`, or `None` once all names are taken, which
+    /// ends the branch.
+    fn fresh(used: &mut HashSet<Var>, v: Var) -> Option<Var> {
+        let v = generate_free_var_gte(used, v).ok()?;
+        used.insert(v);
+        Some(v)
+    }
+
+    fn assume(&self, hyps: &[Hyp], term: Expr, ty: Expr) -> Vec<Hyp> {
+        let mut hyps = hyps.to_vec();
+        if !self.present(&hyps, &ty) {
+            hyps.push(Hyp {
+                term,
+                key: self.key(&ty),
+                shape: self.classify(&ty),
+            });
+        }
+        hyps
+    }
+
+    fn prove(&mut self, hyps: &[Hyp], used: &HashSet<Var>, goal: &Expr) -> Option<Expr> {
+        if let Expr::Pi(pi) = self.whnf(goal)? {
+            // A hypothesis that is already present is not bound again, so the
+            // binder only has to avoid the free variables of the proof.
+            if !pi.2.free_vars().contains(&pi.0) && self.present(hyps, &pi.1) {
+                let t = self.prove(hyps, used, &pi.2)?;
+                let x = generate_free_var_gte(&t.free_vars(), pi.0).ok()?;
+                return Some(lambda(x, pi.1.clone(), t));
+            }
+            let mut used = used.clone();
+            let x = Self::fresh(&mut used, pi.0)?;
+            let body = pi.2.alpha_substitution(pi.0, x.into()).ok()?;
+            let hyps = self.assume(hyps, x.into(), pi.1.clone());
+            let t = self.prove(&hyps, &used, &body)?;
+            return Some(lambda(x, pi.1.clone(), t));
+        }
+
+        let key = (hyps.len(), self.key(goal));
+        if self.path.contains(&key) {
+            return None;
+        }
+        self.path.push(key);
+        let result = self.prove_atom(hyps, used, goal);
+        self.path.pop();
+        result
+    }
+
+    fn prove_atom(&mut self, hyps: &[Hyp], used: &HashSet<Var>, goal: &Expr) -> Option<Expr> {
+        if let Some(h) = self.find(hyps, goal) {
+            return Some(h.term.clone());
+        }
+
+        for Hyp { term: p, shape, .. } in hyps {
+            if let Shape::Bottom = shape {
+                return Some(app(p.clone(), goal.clone()));
+            }
+        }
+
+        for Hyp { term: p, shape, .. } in hyps {
+            let Shape::And(a, b) = shape else { continue };
+            if self.present(hyps, a) && self.present(hyps, b) {
+                continue;
+            }
+            let mut fv = a.free_vars();
+            fv.extend(b.free_vars());
+            let x = generate_free_var_gte(&fv, Var('a')).ok()?;
+            fv.insert(x);
+            let y = generate_free_var_gte(&fv, Var('b')).ok()?;
+            let project = |z: Var, ty: &Expr| {
+                let select = lambda(x, a.clone(), lambda(y, b.clone(), z.into()));
+                app(app(p.clone(), ty.clone()), select)
+            };
+            let hyps = self.assume(hyps, project(x, a), a.clone());
+            let hyps = self.assume(&hyps, project(y, b), b.clone());
+            return self.prove(&hyps, used, goal);
+        }
+
+        for Hyp { term: p, shape, .. } in hyps {
+            let Shape::Or(a, b) = shape else { continue };
+            if self.present(hyps, a) || self.present(hyps, b) {
+                continue;
+            }
+            let mut used = used.clone();
+            let (Some(x), Some(y)) = (
+                Self::fresh(&mut used, Var('x')),
+                Self::fresh(&mut used, Var('y')),
+            ) else {
+                continue;
+            };
+            let left = self.prove(&self.assume(hyps, x.into(), a.clone()), &used, goal);
+            let right = self.prove(&self.assume(hyps, y.into(), b.clone()), &used, goal);
+            if let (Some(left), Some(right)) = (left, right) {
+                let t = app(app(p.clone(), goal.clone()), lambda(x, a.clone(), left));
+                return Some(app(t, lambda(y, b.clone(), right)));
+            }
+        }
+
+        for Hyp { term: p, shape, .. } in hyps {
+            let Shape::Implies(a, b) = shape else {
+                continue;
+            };
+            if self.present(hyps, b) {
+                continue;
+            }
+            let Some(ta) = self.prove(hyps, used, a) else {
+                continue;
+            };
+            let hyps = self.assume(hyps, app(p.clone(), ta), b.clone());
+            if let Some(t) = self.prove(&hyps, used, goal) {
+                return Some(t);
+            }
+        }
+
+        None
+    }
+}
+
+impl Resolver {
+    /// Searches for an inhabitant of the proposition `goal` in the context
+    /// and environment of the judgement `j`. Returns `None` if there is no
+    /// proof in intuitionistic propositional logic.
+    pub fn prove(&self, j: usize, goal: &Expr) -> Option<Expr> {
+        let judgement = &self.judgements[j];
+        let mut search = Search {
+            resolver: self,
+            definitions: judgement.definitions,
+            path: vec![],
+        };
+
+        let mut hyps = vec![];
+        let mut used = goal.free_vars();
+        for (x, a) in self.declarations(judgement.context) {
+            used.insert(x);
+            used.extend(a.free_vars());
+            hyps = search.assume(&hyps, x.into(), a);
+        }
+        search.prove(&hyps, &used, goal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::fixtures::propositions;
+
+    const OR: &str = "?c:(*).(?l:(?a:(A).(c)).(?r:(?b:(B).(c)).(c)))";
+
+    #[rstest]
+    #[case("?x:(A).(A)")]
+    #[case("?x:(A).(?y:(B).(A))")]
+    #[case("implies[(and[(A),(B)]),(and[(B),(A)])]")]
+    #[case("implies[(A),(not[(not[(A)])])]")]
+    #[case("implies[(implies[(A),(B)]),(implies[(not[(B)]),(not[(A)])])]")]
+    #[case("implies[(contra[]),(A)]")]
+    #[case("not[(not[(?p:(not[(not[(A)])]).(A))])]")]
+    #[case(&format!("?p:({OR}).({})", OR.replace("A", "X").replace("B", "A").replace("X", "B")))]
+    #[case(&format!("?p:(and[(A),(not[(A)])]).({OR})"))]
+    fn provable(#[case] goal: &str) {
+        let (mut resolver, anchor) = propositions();
+
+        let goal: Expr = goal.parse().unwrap();
+        let proof = resolver.prove(anchor, &goal).unwrap();
+        let j = resolver.derive(anchor, &proof).unwrap();
+        resolver.convert(anchor, j, &goal).unwrap();
+    }
+
+    #[rstest]
+    #[case("A")]
+    #[case("?p:(not[(not[(A)])]).(A)")]
+    #[case("?p:(implies[(implies[(A),(B)]),(A)]).(A)")]
+    #[case(&format!("?p:(implies[(A),(B)]).({})", OR.replace("A", "not[(A)]")))]
+    fn unprovable(#[case] goal: &str) {
+        let (resolver, anchor) = propositions();
+
+        assert_eq!(resolver.prove(anchor, &goal.parse().unwrap()), None);
+    }
+}
@@ -0,0 +1,349 @@
+//! A line-oriented REPL over a [`Resolver`].
+//!
+//! Every line is one command; [`Repl::eval`] returns the output of a command
+//! as a string, so the REPL can be driven without a terminal.
+
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    book::{Book, parse_expr},
+    de_bruijn, library,
+    model::{Expr, Var},
+    rule::Resolver,
+    verifier::Verifier,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+const HELP: &str = "\
+commands:
+  load <file>     load a def2 book or a derivation log
+  use <name>      load a library book and its dependencies
+  var <x> <A>     declare x : A in the context
+  reset           clear the context
+  context         show the context
+  defs            show the definitions
+  parse <e>       parse and print e
+  type <e>        infer the type of e
+  whnf <e>        weak head normal form of e
+  nf <e>          beta-delta normal form of e
+  unfold <e>      unfold the definition instance e once
+  alpha <a> <b>   alpha-equivalence of a and b
+  conv <a> <b>    beta-delta convertibility of a and b
+  holes <e> [<t>] report the holes _ and ?name of e, checked against t
+  help            show this help";
+
+#[derive(Debug)]
+pub struct Repl {
+    resolver: Resolver,
+    /// A judgement `* : @` in the empty context of the current environment.
+    base: usize,
+    /// A judgement `* : @` in the current context.
+    anchor: usize,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn expr(s: &str) -> Result<Expr> {
+    parse_expr(s.trim())
+}
+
+fn two_exprs(s: &str) -> Result<(Expr, Expr)> {
+    match s.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [a, b] => Ok((expr(a)?, expr(b)?)),
+        _ => Err("expected two expressions".to_string()),
+    }
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        let mut resolver = Resolver::new();
+        let base = resolver.sort();
+        Self {
+            resolver,
+            base,
+            anchor: base,
+        }
+    }
+
+    pub fn resolver(&self) -> &Resolver {
+        &self.resolver
+    }
+
+    fn definitions(&self) -> usize {
+        self.resolver.judgements[self.base].definitions
+    }
+
+    fn context(&self) -> Vec<(Var, Expr)> {
+        self.resolver
+            .declarations(self.resolver.judgements[self.anchor].context)
+    }
+
+    fn delta(&self) -> impl Fn(&de_bruijn::Definition) -> Option<de_bruijn::Expr> {
+        let definitions = self.definitions();
+        move |d| self.resolver.unfold(definitions, d)
+    }
+
+    /// Type-checks `e` in the current context, returning its elaborated
+    /// form. Normalization only terminates on well-typed terms.
+    fn checked(&mut self, e: &Expr) -> Result<Expr> {
+        let j = self.resolver.derive(self.anchor, e)?;
+        Ok(self.resolver.judgements[j].m.clone())
+    }
+
+    fn load(&mut self, path: &str) -> Result<String> {
+        let input = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let context = self.context();
+
+        if input.trim_start().starts_with("def2") {
+            let book: Book = input.parse()?;
+            self.base = self
+                .resolver
+                .load(self.base, &book)
+                .map_err(|e| e.to_string())?;
+        } else {
+            // The log is verified on its own and its definitions are then
+            // rechecked on top of the ones already loaded.
+            let verifier = Verifier::try_run(&input).map_err(|e| format!("{path}: {e}"))?;
+            let logged = verifier.resolver();
+            let definitions = logged.judgements.last().map_or(0, |j| j.definitions);
+            let book = logged.book(definitions);
+            self.base = self
+                .resolver
+                .load(self.base, &book)
+                .map_err(|e| format!("{path}: {e}"))?;
+        }
+
+        self.anchor = self.resolver.start(self.base, &context)?;
+        let names = self.resolver.book(self.definitions()).0.len();
+        Ok(format!("{names} definitions"))
+    }
+
+    fn use_library(&mut self, name: &str) -> Result<String> {
+        let book = library::book(name).ok_or_else(|| {
+            let names = library::names().collect::<Vec<_>>().join(", ");
+            format!("no library book {name}, try one of {names}")
+        })?;
+        let defined = self.resolver.book(self.definitions());
+        let book = Book(
+            book.0
+                .into_iter()
+                .filter(|b| defined.get(&b.name).is_none())
+                .collect(),
+        );
+        let context = self.context();
+        self.base = self
+            .resolver
+            .load(self.base, &book)
+            .map_err(|e| e.to_string())?;
+        self.anchor = self.resolver.start(self.base, &context)?;
+        Ok(format!("{} definitions", book.0.len()))
+    }
+
+    /// Runs one command, returning its output.
+    pub fn eval(&mut self, line: &str) -> Result<String> {
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+
+        match command {
+            "" => Ok(String::new()),
+            "help" => Ok(HELP.to_string()),
+            "load" => self.load(rest.trim()),
+            "use" => self.use_library(rest.trim()),
+            "var" => {
+                let (x, a) = rest
+                    .trim()
+                    .split_once(' ')
+                    .ok_or("expected a variable and its type")?;
+                let x = x.parse::<Var>()?;
+                let a = expr(a)?;
+                self.anchor = self.resolver.start(self.anchor, &[(x, a.clone())])?;
+                Ok(format!("{} : {}", x.0, a.plain()))
+            }
+            "reset" => {
+                self.anchor = self.base;
+                Ok(String::new())
+            }
+            "context" => Ok(self
+                .context()
+                .iter()
+                .map(|(x, a)| format!("{} : {}", x.0, a.plain()))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            "defs" => Ok(self
+                .resolver
+                .book(self.definitions())
+                .0
+                .iter()
+                .map(|b| format!("{} : {}", b.name, b.n.plain()))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            "parse" => Ok(expr(rest)?.plain().to_string()),
+            "type" => {
+                let e = expr(rest)?;
+                let j = self.resolver.derive(self.anchor, &e)?;
+                Ok(self.resolver.judgements[j].n.plain().to_string())
+            }
+            "whnf" => {
+                let e = self.checked(&expr(rest)?)?;
+                Ok(self
+                    .resolver
+                    .whnf(self.definitions(), &e)?
+                    .plain()
+                    .to_string())
+            }
+            "nf" => {
+                let e = self.checked(&expr(rest)?)?;
+                let e: Expr = e.de_bruijn().normalize_with(&self.delta()).try_into()?;
+                Ok(e.plain().to_string())
+            }
+            "unfold" => {
+                let Expr::Definition(d) = expr(rest)? else {
+                    return Err("expected a definition instance".to_string());
+                };
+                let e = Expr::Definition(d).de_bruijn();
+                let de_bruijn::Expr::Definition(d) = &e else {
+                    unreachable!();
+                };
+                let unfolded: Expr = self
+                    .resolver
+                    .unfold(self.definitions(), d)
+                    .ok_or_else(|| format!("{} cannot be unfolded", d.0))?
+                    .try_into()?;
+                Ok(unfolded.plain().to_string())
+            }
+            "alpha" => {
+                let (a, b) = two_exprs(rest)?;
+                Ok(a.alpha_eq(&b).to_string())
+            }
+            "conv" => {
+                let (a, b) = two_exprs(rest)?;
+                let (a, b) = (self.checked(&a)?, self.checked(&b)?);
+                Ok(self
+                    .resolver
+                    .convertible(self.definitions(), &a, &b)
+                    .to_string())
+            }
+            "holes" => {
+                let (e, ty) = match rest.split_whitespace().collect::<Vec<_>>().as_slice() {
+                    [e] => (expr(e)?, None),
+                    [e, ty] => (expr(e)?, Some(expr(ty)?)),
+                    _ => return Err("expected an expression and optionally its type".to_string()),
+                };
+                let goals = self.resolver.holes(self.anchor, &e, ty.as_ref())?;
+                if goals.is_empty() {
+                    return Ok("no holes".to_string());
+                }
+                Ok(goals
+                    .iter()
+                    .map(|g| g.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            command => Err(format!("unknown command {command}, try help")),
+        }
+    }
+
+    /// Evaluates every line of `input`, writing outputs and errors to
+    /// `output`.
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        for line in input.lines() {
+            match self.eval(&line?) {
+                Ok(s) if s.is_empty() => {}
+                Ok(s) => writeln!(output, "{s}")?,
+                Err(e) => writeln!(output, "error: {e}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn repl() -> Repl {
+        let mut repl = Repl::new();
+        repl.eval("load check/def2").unwrap();
+        repl.eval("var A *").unwrap();
+        repl.eval("var B *").unwrap();
+        repl
+    }
+
+    #[rstest]
+    #[case("parse $x:(A).(x)", "$x:(A).(x)")]
+    #[case("type $x:(A).(x)", "?x:(A).(A)")]
+    #[case("type not[(A)]", "*")]
+    #[case("whnf not[(A)]", "?a:(A).(contra[])")]
+    #[case("nf not[(A)]", "?a:(A).(?x:(*).(x))")]
+    #[case("unfold not[(B)]", "implies[(B),(contra[])]")]
+    #[case("alpha $x:(A).(x) $y:(A).(y)", "true")]
+    #[case("conv not[(A)] implies[(A),(contra[])]", "true")]
+    #[case("conv A B", "false")]
+    #[case("context", "A : *\nB : *")]
+    #[case("holes $x:(A).(?body) ?x:(A).(A)", "A : *, B : *, x : A |- ?body : A")]
+    #[case("holes $x:(A).(x)", "no holes")]
+    fn eval(#[case] line: &str, #[case] output: &str) {
+        assert_eq!(repl().eval(line).unwrap(), output);
+    }
+
+    #[rstest]
+    #[case("frob", "unknown command")]
+    #[case("type C", "not declared")]
+    #[case("var A *", "not fresh")]
+    #[case("unfold A", "expected a definition instance")]
+    #[case("alpha A", "expected two expressions")]
+    #[case("holes", "expected an expression")]
+    #[case("nf %($x:(*).(%(x)(x)))($x:(*).(%(x)(x)))", "is not a product")]
+    #[case("whnf C", "not declared")]
+    #[case("conv A %(*)(*)", "is not a product")]
+    #[case("use missing", "no library book missing, try one of logic")]
+    #[case("load check/missing", "check/missing")]
+    fn errors(#[case] line: &str, #[case] message: &str) {
+        let error = repl().eval(line).unwrap_err();
+        assert!(error.contains(message), "{error}");
+    }
+
+    #[test]
+    fn use_library() {
+        let mut repl = Repl::new();
+        repl.eval("var A *").unwrap();
+        assert_eq!(repl.eval("use quantifiers").unwrap(), "37 definitions");
+        assert_eq!(repl.eval("use classical").unwrap(), "4 definitions");
+        assert_eq!(
+            repl.eval("type excluded_middle[(A)]").unwrap(),
+            "or[(A),(not[(A)])]"
+        );
+    }
+
+    #[test]
+    fn load_log_keeps_definitions() {
+        let mut repl = Repl::new();
+        repl.eval("use logic").unwrap();
+        repl.eval("var A *").unwrap();
+        repl.eval("var B *").unwrap();
+        let error = repl.eval("load check/log").unwrap_err();
+        assert!(error.contains("implies is already defined"), "{error}");
+        assert_eq!(repl.eval("type not[(A)]").unwrap(), "*");
+        assert_eq!(repl.eval("defs").unwrap().lines().count(), 29);
+    }
+
+    #[test]
+    fn run() {
+        let input =
+            "load check/log\nvar A *\ntype implies[(A),(A)]\n\ndefs\nreset\ncontext\ntype A\n";
+        let mut output = vec![];
+        Repl::new().run(input.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "3 definitions\nA : *\n*\nimplies : *\nand : *\nand_in : and[(A),(B)]\n\
+             error: A is not declared in the context\n"
+        );
+    }
+}
@@ -1,16 +1,62 @@
-use crate::in_tree::{Entry, InTree};
+use crate::in_tree::{InTree, Keyed};
 
 use super::model::*;
 
+type Result<T> = std::result::Result<T, String>;
+
 #[derive(Debug)]
 pub struct Resolver {
     pub judgements: Vec<Judgement>,
+    /// The rule that derived each judgement.
+    pub rules: Vec<Rule>,
     pub context: InTree<(Var, Expr)>,
     pub definitions: InTree<DefinitionEntry>,
 }
 
+/// An application of a λD rule, referring to its premises by judgement index.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Book(Vec<Judgement>);
+pub enum Rule {
+    Sort,
+    Var(usize, Var),
+    Weak(usize, usize, Var),
+    Form(usize, usize),
+    Appl(usize, usize),
+    Abst(usize, usize),
+    Conv(usize, usize),
+    Def(usize, usize, String),
+    DefPrim(usize, usize, String),
+    /// The definition is given by its position, as for [`Resolver::inst_ix`].
+    Inst(usize, Vec<usize>, usize),
+    Cp(usize),
+    Sp(usize, usize),
+}
+
+/// The instruction of the numeric log format.
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rule::Sort => write!(f, "sort"),
+            Rule::Var(a, x) => write!(f, "var {a} {}", x.0),
+            Rule::Weak(a, b, x) => write!(f, "weak {a} {b} {}", x.0),
+            Rule::Form(a, b) => write!(f, "form {a} {b}"),
+            Rule::Appl(a, b) => write!(f, "appl {a} {b}"),
+            Rule::Abst(a, b) => write!(f, "abst {a} {b}"),
+            Rule::Conv(a, b) => write!(f, "conv {a} {b}"),
+            Rule::Def(a, b, name) => write!(f, "def {a} {b} {name}"),
+            Rule::DefPrim(a, b, name) => write!(f, "defpr {a} {b} {name}"),
+            Rule::Inst(a, args, d) => {
+                write!(f, "inst {a} {}", args.len())?;
+                for arg in args {
+                    write!(f, " {arg}")?;
+                }
+                write!(f, " {d}")
+            }
+            Rule::Cp(a) => write!(f, "cp {a}"),
+            Rule::Sp(a, i) => write!(f, "sp {a} {i}"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Judgement {
     pub definitions: usize,
@@ -27,6 +73,32 @@ pub struct DefinitionEntry {
     pub n: Expr,
 }
 
+impl Keyed for (Var, Expr) {
+    type Key = (Var, crate::de_bruijn::Expr);
+
+    fn key(&self) -> Self::Key {
+        (self.0, self.1.de_bruijn())
+    }
+}
+
+impl Keyed for DefinitionEntry {
+    type Key = (
+        usize,
+        String,
+        Option<crate::de_bruijn::Expr>,
+        crate::de_bruijn::Expr,
+    );
+
+    fn key(&self) -> Self::Key {
+        (
+            self.context,
+            self.name.clone(),
+            self.m.as_ref().map(Expr::de_bruijn),
+            self.n.de_bruijn(),
+        )
+    }
+}
+
 impl Default for Resolver {
     fn default() -> Self {
         Self::new()
@@ -37,86 +109,227 @@ impl Resolver {
     pub fn new() -> Self {
         Self {
             judgements: vec![],
+            rules: vec![],
             context: InTree::new(),
             definitions: InTree::new(),
         }
     }
 
+    fn push(&mut self, judgement: Judgement, rule: Rule) -> usize {
+        self.judgements.push(judgement);
+        self.rules.push(rule);
+        self.judgements.len() - 1
+    }
+
+    /// The derivations of all judgements in the numeric log format accepted
+    /// by [`crate::verifier::Verifier::run`], terminated by `-1`.
+    pub fn log(&self) -> String {
+        self.rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| format!("{i} {rule}\n"))
+            .chain(["-1\n".to_string()])
+            .collect()
+    }
+
+    /// The λD rules only allow extending a context with a variable that is
+    /// not declared in it yet.
+    fn fresh(&self, context: usize, var: Var) -> Result<()> {
+        match self.context.resolve(context, |(v, _)| *v == var) {
+            Some((_, ty)) => Err(format!(
+                "{var} is not fresh: the context already declares {var}:{ty}"
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Describes the first difference between two contexts, comparing
+    /// declarations pairwise with types up to alpha-equivalence.
+    pub fn context_mismatch(&self, a: usize, b: usize) -> Option<String> {
+        let (len_a, len_b) = (self.context.len(a), self.context.len(b));
+        if len_a != len_b {
+            return Some(format!(
+                "contexts declare {len_a} and {len_b} variables respectively"
+            ));
+        }
+
+        let (mut a, mut b) = (a, b);
+        for position in (0..len_a).rev() {
+            if a == b {
+                return None;
+            }
+            let ea = self.context.get(a).unwrap();
+            let eb = self.context.get(b).unwrap();
+            let ((xa, ta), (xb, tb)) = (&ea.value, &eb.value);
+            if xa != xb || !ta.alpha_eq(tb) {
+                return Some(format!(
+                    "declaration {position} differs: {xa}:{ta} versus {xb}:{tb}"
+                ));
+            }
+            (a, b) = (ea.parent_index, eb.parent_index);
+        }
+
+        None
+    }
+
+    pub fn same_context(&self, a: usize, b: usize) -> bool {
+        self.context_mismatch(a, b).is_none()
+    }
+
+    /// Checks that the context `extended` is `base` followed by exactly one
+    /// declaration, and returns that declaration.
+    fn context_extension(&self, base: usize, extended: usize) -> Result<&(Var, Expr)> {
+        let Some(entry) = self.context.get(extended) else {
+            return Err("expected the context to extend the base context by one declaration, found an empty context".to_string());
+        };
+        if let Some(mismatch) = self.context_mismatch(base, entry.parent_index) {
+            let (x, a) = &entry.value;
+            return Err(format!(
+                "context of {x}:{a} does not extend the base context: {mismatch}"
+            ));
+        }
+        Ok(&entry.value)
+    }
+
+    /// Drops the judgements from `len` on, undoing a derivation that failed
+    /// partway.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.judgements.truncate(len);
+        self.rules.truncate(len);
+    }
+
     pub fn sort(&mut self) -> usize {
-        self.judgements.push(Judgement {
+        let rule = Rule::Sort;
+        let judgement = Judgement {
             definitions: 0,
             context: 0,
             m: Expr::Asterisk,
             n: Expr::Square,
-        });
-        self.judgements.len() - 1
+        };
+        self.push(judgement, rule)
     }
 
     pub fn var(&mut self, j: usize, var: Var) -> usize {
+        premises(self.try_var(j, var))
+    }
+
+    pub fn weak(&mut self, a: usize, b: usize, var: Var) -> usize {
+        premises(self.try_weak(a, b, var))
+    }
+
+    pub fn form(&mut self, a: usize, b: usize) -> usize {
+        premises(self.try_form(a, b))
+    }
+
+    pub fn appl(&mut self, e1: usize, e2: usize) -> usize {
+        premises(self.try_appl(e1, e2))
+    }
+
+    pub fn abst(&mut self, e1: usize, e2: usize) -> usize {
+        premises(self.try_abst(e1, e2))
+    }
+
+    pub fn conv(&mut self, e1: usize, e2: usize) -> usize {
+        premises(self.try_conv(e1, e2))
+    }
+
+    pub fn def(&mut self, e1: usize, e2: usize, name: String) -> usize {
+        premises(self.try_def(e1, e2, name))
+    }
+
+    pub fn def_prim(&mut self, e1: usize, e2: usize, name: String) -> usize {
+        premises(self.try_def_prim(e1, e2, name))
+    }
+
+    pub fn inst_ix(&mut self, e1: usize, e2: &[usize], d: usize) -> usize {
+        premises(self.try_inst_ix(e1, e2, d))
+    }
+
+    pub fn inst(&mut self, e1: usize, e2: &[usize], name: String) -> usize {
+        premises(self.try_inst(e1, e2, name))
+    }
+
+    pub fn cp(&mut self, j: usize) -> usize {
+        let judgement = self.judgements[j].clone();
+        self.push(judgement, Rule::Cp(j))
+    }
+
+    pub fn sp(&mut self, j: usize, ix: usize) -> usize {
+        premises(self.try_sp(j, ix))
+    }
+
+    pub fn try_var(&mut self, j: usize, var: Var) -> Result<usize> {
+        let rule = Rule::Var(j, var);
+        let judgement = &self.judgements[j];
+        is_type(judgement)?;
+        self.fresh(judgement.context, var)?;
         let Judgement {
             definitions,
             context,
             m,
-            n,
-        } = &self.judgements[j];
-        assert!(n.is_sort());
-        self.judgements.push(Judgement {
+            ..
+        } = judgement;
+        let judgement = Judgement {
             definitions: *definitions,
             context: self.context.create(*context, (var, m.clone())),
             m: Expr::Var(var),
             n: m.clone(),
-        });
-        self.judgements.len() - 1
+        };
+        Ok(self.push(judgement, rule))
     }
 
-    pub fn weak(&mut self, a: usize, b: usize, var: Var) -> usize {
+    pub fn try_weak(&mut self, a: usize, b: usize, var: Var) -> Result<usize> {
+        let rule = Rule::Weak(a, b, var);
         let a = &self.judgements[a];
         let b = &self.judgements[b];
-        assert_eq!(a.definitions, b.definitions);
-        assert_eq!(a.context, b.context);
-        assert!(b.n.is_sort());
+        same_definitions(a, b)?;
+        same_context(a, b)?;
+        is_type(b)?;
+        self.fresh(a.context, var)?;
 
-        self.judgements.push(Judgement {
+        let judgement = Judgement {
             definitions: a.definitions,
             context: self.context.create(a.context, (var, b.m.clone())),
             m: a.m.clone(),
             n: a.n.clone(),
-        });
-        self.judgements.len() - 1
+        };
+        Ok(self.push(judgement, rule))
     }
 
-    pub fn form(&mut self, a: usize, b: usize) -> usize {
+    pub fn try_form(&mut self, a: usize, b: usize) -> Result<usize> {
+        let rule = Rule::Form(a, b);
         let a = &self.judgements[a];
         let b = &self.judgements[b];
 
-        assert_eq!(a.definitions, b.definitions);
-        assert!(a.n.is_sort());
-        assert!(b.n.is_sort());
+        same_definitions(a, b)?;
+        is_type(a)?;
+        is_type(b)?;
 
-        let entry = self.context.get(b.context).unwrap();
-        assert_eq!(a.context, entry.parent_index);
-        let (ref var, ref ty) = entry.value;
-        assert_eq!(ty, &a.m);
+        let (var, ty) = self.context_extension(a.context, b.context)?;
+        if !ty.alpha_eq(&a.m) {
+            return Err(format!("expected {var}:{}, found {var}:{ty}", a.m));
+        }
 
-        self.judgements.push(Judgement {
+        let judgement = Judgement {
             definitions: a.definitions,
             context: a.context,
-            m: Expr::Pi(Box::new(Pi(*var, a.m.clone(), b.m.clone()))),
+            m: Pi(*var, a.m.clone(), b.m.clone()).into(),
             n: b.n.clone(),
-        });
-        self.judgements.len() - 1
+        };
+        Ok(self.push(judgement, rule))
     }
 
-    pub fn appl(&mut self, e1: usize, e2: usize) -> usize {
+    pub fn try_appl(&mut self, e1: usize, e2: usize) -> Result<usize> {
+        let rule = Rule::Appl(e1, e2);
         let e1 = &self.judgements[e1];
         let e2 = &self.judgements[e2];
 
-        assert_eq!(e1.definitions, e2.definitions);
-        assert_eq!(e1.context, e2.context);
+        same_definitions(e1, e2)?;
+        same_context(e1, e2)?;
 
         let m = &e1.m;
         let Expr::Pi(pi) = &e1.n else {
-            panic!("expected Pi");
+            return Err(format!("{m} has type {}, expected a Pi type", e1.n));
         };
         let x = pi.0;
         let a1 = &pi.1;
@@ -124,80 +337,88 @@ impl Resolver {
 
         let n = &e2.m;
         let a2 = &e2.n;
-        // assert_eq!(a1, a2);
+        if !a1.alpha_eq(a2) {
+            return Err(format!("the argument {n} must have type {a1}, found {a2}"));
+        }
 
-        self.judgements.push(Judgement {
+        let judgement = Judgement {
             definitions: e1.definitions,
             context: e1.context,
-            m: Expr::Application(Box::new(Application(m.clone(), n.clone()))),
-            n: b.alpha_substitution(x, n.clone()),
-        });
-        self.judgements.len() - 1
+            m: Application(m.clone(), n.clone()).into(),
+            n: b.alpha_substitution(x, n.clone())?,
+        };
+        Ok(self.push(judgement, rule))
     }
 
-    pub fn abst(&mut self, e1: usize, e2: usize) -> usize {
+    pub fn try_abst(&mut self, e1: usize, e2: usize) -> Result<usize> {
+        let rule = Rule::Abst(e1, e2);
         let e1 = &self.judgements[e1];
         let e2 = &self.judgements[e2];
 
-        assert_eq!(e1.definitions, e2.definitions);
-        let Entry {
-            parent_index: e1_context_parent,
-            value: (x1, a1),
-            ..
-        } = self.context.get(e1.context).unwrap();
-        assert_eq!(*e1_context_parent, e2.context);
+        same_definitions(e1, e2)?;
+        let (x1, a1) = self.context_extension(e2.context, e1.context)?;
 
         let m = &e1.m;
         let b1 = &e1.n;
 
         let Expr::Pi(pi) = &e2.m else {
-            panic!("Expected Pi");
+            return Err(format!("expected a Pi type, found {}", e2.m));
         };
         let x2 = pi.0;
         let a2 = &pi.1;
         let b2 = &pi.2;
 
-        assert_eq!(*x1, x2);
-        assert_eq!(a1, a2);
-        // assert_eq!(b1, b2);
-        assert!(e2.n.is_sort());
+        if *x1 != x2 {
+            return Err(format!("abstraction binds {x1} but the Pi type binds {x2}"));
+        }
+        if !a1.alpha_eq(a2) {
+            return Err(format!("expected {x1}:{a2}, found {x1}:{a1}"));
+        }
+        if !b1.alpha_eq(b2) {
+            return Err(format!(
+                "the body {m} has type {b1}, but the codomain of {} is {b2}",
+                e2.m
+            ));
+        }
+        is_type(e2)?;
 
-        self.judgements.push(Judgement {
+        let judgement = Judgement {
             definitions: e1.definitions,
             context: e2.context,
             m: Lambda(*x1, a1.clone(), m.clone()).into(),
-            n: Pi(*x1, a2.clone(), b1.clone()).into(),
-        });
-        self.judgements.len() - 1
+            n: e2.m.clone(),
+        };
+        Ok(self.push(judgement, rule))
     }
 
-    pub fn conv(&mut self, e1: usize, e2: usize) -> usize {
+    pub fn try_conv(&mut self, e1: usize, e2: usize) -> Result<usize> {
+        let rule = Rule::Conv(e1, e2);
         let e1 = &self.judgements[e1];
         let e2 = &self.judgements[e2];
 
-        assert_eq!(e1.definitions, e2.definitions);
-        assert_eq!(e1.context, e2.context);
+        same_definitions(e1, e2)?;
+        same_context(e1, e2)?;
         let a = &e1.m;
         // let b1 = e1.n;
 
         let b2 = &e2.m;
-        let s = &e2.n;
-        assert!(s.is_sort());
+        is_type(e2)?;
 
-        self.judgements.push(Judgement {
+        let judgement = Judgement {
             definitions: e1.definitions,
             context: e1.context,
             m: a.clone(),
             n: b2.clone(),
-        });
-        self.judgements.len() - 1
+        };
+        Ok(self.push(judgement, rule))
     }
 
-    pub fn def(&mut self, e1: usize, e2: usize, name: String) -> usize {
+    pub fn try_def(&mut self, e1: usize, e2: usize, name: String) -> Result<usize> {
+        let rule = Rule::Def(e1, e2, name.clone());
         let e1 = &self.judgements[e1];
         let e2 = &self.judgements[e2];
 
-        assert_eq!(e1.definitions, e2.definitions);
+        same_definitions(e1, e2)?;
         let context = e1.context;
 
         let k = &e1.m;
@@ -205,12 +426,9 @@ impl Resolver {
         let m = &e2.m;
         let n = &e2.n;
 
-        assert_eq!(
-            self.definitions.resolve(e1.definitions, |d| d.name == name),
-            None,
-        );
+        self.undefined(e1.definitions, &name)?;
 
-        self.judgements.push(Judgement {
+        let judgement = Judgement {
             definitions: self.definitions.create(
                 e1.definitions,
                 DefinitionEntry {
@@ -223,28 +441,25 @@ impl Resolver {
             context,
             m: k.clone(),
             n: l.clone(),
-        });
-        self.judgements.len() - 1
+        };
+        Ok(self.push(judgement, rule))
     }
 
-    pub fn def_prim(&mut self, e1: usize, e2: usize, name: String) -> usize {
+    pub fn try_def_prim(&mut self, e1: usize, e2: usize, name: String) -> Result<usize> {
+        let rule = Rule::DefPrim(e1, e2, name.clone());
         let e1 = &self.judgements[e1];
         let e2 = &self.judgements[e2];
 
-        assert_eq!(e1.definitions, e2.definitions);
+        same_definitions(e1, e2)?;
 
         let k = &e1.m;
         let l = &e1.n;
         let n = &e2.m;
-        let s = &e2.n;
 
-        assert!(s.is_sort());
-        assert_eq!(
-            self.definitions.resolve(e1.definitions, |d| d.name == name),
-            None,
-        );
+        is_type(e2)?;
+        self.undefined(e1.definitions, &name)?;
 
-        self.judgements.push(Judgement {
+        let judgement = Judgement {
             definitions: self.definitions.create(
                 e1.definitions,
                 DefinitionEntry {
@@ -257,136 +472,157 @@ impl Resolver {
             context: e1.context,
             m: k.clone(),
             n: l.clone(),
-        });
-        self.judgements.len() - 1
+        };
+        Ok(self.push(judgement, rule))
     }
 
-    pub fn inst_ix(&mut self, e1: usize, e2: &[usize], d: usize) -> usize {
-        let e1 = &self.judgements[e1];
-
-        let entry = self.definitions.traverse(e1.definitions, d).unwrap();
-        let d = &entry.value;
-
-        assert_eq!(self.context.len(d.context), e2.len());
-
-        let mut n = d.n.clone();
-
-        let mut values = vec![];
+    fn undefined(&self, definitions: usize, name: &str) -> Result<()> {
+        match self.definitions.resolve(definitions, |d| d.name == name) {
+            Some(_) => Err(format!("{name} is already defined")),
+            None => Ok(()),
+        }
+    }
 
-        let mut c = d.context;
+    pub fn try_inst_ix(&mut self, e1: usize, e2: &[usize], d: usize) -> Result<usize> {
+        let definitions = self.judgements[e1].definitions;
+        let Some(entry) = self.definitions.traverse(definitions, d) else {
+            return Err(format!("there is no definition with index {d}"));
+        };
+        let d = entry.value.clone();
 
-        for e in e2.iter().rev() {
-            let e = &self.judgements[*e];
-            let Entry {
-                parent_index,
-                value: (v, a),
-                ..
-            } = self.context.get(c).unwrap();
-            c = *parent_index;
-
-            assert_eq!(e1.definitions, e.definitions);
-            assert_eq!(e1.context, e.context);
-
-            let u = &e.m;
-            let a_substituted = &e.n;
-            // assert_eq!(
-            //     a.alpha_substitution(*v, u.clone()).de_bruijn(),
-            //     a_substituted.de_bruijn()
-            // );
-
-            n = n.alpha_substitution(*v, u.clone());
-
-            values.push(u.clone());
-        }
+        self.instantiate(e1, e2, &d)
+    }
 
-        values.reverse();
+    pub fn try_inst(&mut self, e1: usize, e2: &[usize], name: String) -> Result<usize> {
+        let definitions = self.judgements[e1].definitions;
+        let Some(d) = self.definitions.resolve(definitions, |d| d.name == name) else {
+            return Err(format!("unknown definition {name}"));
+        };
+        let d = d.clone();
 
-        assert_eq!(e1.m, Expr::Asterisk);
-        assert_eq!(e1.n, Expr::Square);
+        self.instantiate(e1, e2, &d)
+    }
 
-        self.judgements.push(Judgement {
-            definitions: e1.definitions,
-            context: e1.context,
-            m: Expr::Definition(crate::model::Definition(d.name.clone(), values)),
-            n,
-        });
-        self.judgements.len() - 1
+    /// Position of the definition `name` on the path `definitions`, as
+    /// accepted by [`Resolver::inst_ix`].
+    pub fn definition_index(&self, definitions: usize, name: &str) -> Option<usize> {
+        self.definitions
+            .path(definitions)
+            .iter()
+            .rposition(|d| d.name == name)
     }
 
-    pub fn inst(&mut self, e1: usize, e2: &[usize], name: String) -> usize {
+    /// The arguments replace the parameters in the type of `d`
+    /// simultaneously, so an argument mentioning a later parameter's name is
+    /// not substituted again.
+    fn instantiate(&mut self, e1: usize, e2: &[usize], d: &DefinitionEntry) -> Result<usize> {
+        let definitions = self.judgements[e1].definitions;
+        let index = self.definition_index(definitions, &d.name).unwrap();
+        let rule = Rule::Inst(e1, e2.to_vec(), index);
         let e1 = &self.judgements[e1];
 
-        let d = self
-            .definitions
-            .resolve(e1.definitions, |d| d.name == name)
-            .unwrap();
+        let arity = self.context.len(d.context);
+        if arity != e2.len() {
+            return Err(format!(
+                "{} expects {arity} arguments, found {}",
+                d.name,
+                e2.len()
+            ));
+        }
 
-        assert_eq!(self.context.get(d.context).unwrap().len, e2.len());
+        let parameters = self.declarations(d.context);
+        let mut substitution = vec![];
+        let mut values = vec![];
 
-        let mut n = d.n.clone();
+        for ((v, a), e) in parameters.iter().zip(e2) {
+            let e = &self.judgements[*e];
 
-        let mut values = vec![];
+            same_definitions(e1, e)?;
+            same_context(e1, e)?;
 
-        let mut c = d.context;
+            let expected = a.substitute(&substitution)?;
+            if !e.n.alpha_eq(&expected) {
+                return Err(format!(
+                    "argument {v} of {} must have type {expected}, found {}",
+                    d.name, e.n
+                ));
+            }
 
-        for e in e2.iter().rev() {
-            let e = &self.judgements[*e];
-            let Entry {
-                parent_index,
-                value: (v, a),
-                ..
-            } = self.context.get(c).unwrap();
-            c = *parent_index;
-
-            assert_eq!(e1.definitions, e.definitions);
-            assert_eq!(e1.context, e.context);
-
-            let u = &e.m;
-            let a_substituted = &e.n;
-            // assert_eq!(
-            //     a.alpha_substitution(*v, u.clone()).de_bruijn(),
-            //     a_substituted.de_bruijn()
-            // );
-
-            n = n.alpha_substitution(*v, u.clone());
-
-            values.push(u.clone());
+            substitution.push((*v, e.m.clone()));
+            values.push(e.m.clone());
         }
 
-        values.reverse();
+        let n = d.n.substitute(&substitution)?;
 
-        assert_eq!(e1.m, Expr::Asterisk);
-        assert_eq!(e1.n, Expr::Square);
+        if e1.m != Expr::Asterisk || e1.n != Expr::Square {
+            return Err(format!("inst starts from * : @, found {} : {}", e1.m, e1.n));
+        }
 
-        self.judgements.push(Judgement {
+        let judgement = Judgement {
             definitions: e1.definitions,
             context: e1.context,
-            m: Expr::Definition(crate::model::Definition(name, values)),
+            m: crate::model::Definition(d.name.clone(), values).into(),
             n,
-        });
-        self.judgements.len() - 1
-    }
-
-    pub fn cp(&mut self, j: usize) -> usize {
-        let j = &self.judgements[j];
-        self.judgements.push(j.clone());
-        self.judgements.len() - 1
+        };
+        Ok(self.push(judgement, rule))
     }
 
-    pub fn sp(&mut self, j: usize, ix: usize) -> usize {
+    pub fn try_sp(&mut self, j: usize, ix: usize) -> Result<usize> {
+        let rule = Rule::Sp(j, ix);
         let j = &self.judgements[j];
 
-        let context = self.context.traverse(j.context, ix).unwrap();
+        let Some(context) = self.context.traverse(j.context, ix) else {
+            return Err(format!("the context has no declaration {ix}"));
+        };
 
         let (m, n) = &context.value;
 
-        self.judgements.push(Judgement {
+        let judgement = Judgement {
             definitions: j.definitions,
             context: j.context,
             m: (*m).into(),
             n: n.clone(),
-        });
-        self.judgements.len() - 1
+        };
+        Ok(self.push(judgement, rule))
+    }
+}
+
+/// The plain rules panic when their premises do not hold, which is a bug in
+/// the caller; the `try_` forms report it as an error instead.
+fn premises(result: Result<usize>) -> usize {
+    result.unwrap_or_else(|e| panic!("{e}"))
+}
+
+fn same_definitions(a: &Judgement, b: &Judgement) -> Result<()> {
+    if a.definitions == b.definitions {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} and {} are derived under different definitions",
+            a.m, b.m
+        ))
+    }
+}
+
+fn same_context(a: &Judgement, b: &Judgement) -> Result<()> {
+    if a.context == b.context {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} and {} are derived in different contexts",
+            a.m, b.m
+        ))
+    }
+}
+
+fn is_type(j: &Judgement) -> Result<()> {
+    if j.n.is_sort() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} is not a type: its type {} is not a sort",
+            j.m, j.n
+        ))
     }
 }
 
@@ -412,4 +648,182 @@ mod tests {
 
         eprintln!("{book:?}");
     }
+
+    #[test]
+    fn log_replays_verified_log() {
+        let log = include_str!("../check/log");
+        let verifier = crate::verifier::Verifier::run(log);
+        assert_eq!(verifier.resolver().log(), log);
+    }
+
+    #[test]
+    fn inst_substitutes_arguments_simultaneously() {
+        let book: crate::book::Book = "def2\n2\nA\n*\nB\n*\nf\n#\n?x:(A).(B)\nedef2\nEND"
+            .parse()
+            .unwrap();
+        let (mut resolver, anchor) = book.check().unwrap();
+        let j = resolver
+            .start(
+                anchor,
+                &[(Var('B'), Expr::Asterisk), (Var('A'), Expr::Asterisk)],
+            )
+            .unwrap();
+        let b = resolver.derive(j, &Expr::Var(Var('B'))).unwrap();
+        let a = resolver.derive(j, &Expr::Var(Var('A'))).unwrap();
+
+        // Substituting one parameter after the other would turn the type
+        // into B -> B.
+        let f = resolver.inst(j, &[b, a], "f".to_string());
+        assert!(
+            resolver.judgements[f]
+                .n
+                .alpha_eq(&"?x:(B).(A)".parse().unwrap())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "argument B of f must have type *")]
+    fn inst_checks_argument_types() {
+        let book: crate::book::Book = "def2\n2\nA\n*\nB\n*\nf\n#\n?x:(A).(B)\nedef2\nEND"
+            .parse()
+            .unwrap();
+        let (mut resolver, anchor) = book.check().unwrap();
+        let j = resolver
+            .start(
+                anchor,
+                &[(Var('A'), Expr::Asterisk), (Var('a'), Var('A').into())],
+            )
+            .unwrap();
+        let a = resolver.derive(j, &Expr::Var(Var('A'))).unwrap();
+        let x = resolver.derive(j, &Expr::Var(Var('a'))).unwrap();
+        resolver.inst(j, &[a, x], "f".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "is not fresh")]
+    fn var_requires_fresh_variable() {
+        let mut book = Resolver::new();
+
+        book.sort();
+        book.var(0, Var('A'));
+        book.var(1, Var('A'));
+    }
+
+    #[test]
+    #[should_panic(expected = "is not fresh")]
+    fn weak_requires_fresh_variable() {
+        let mut book = Resolver::new();
+
+        book.sort();
+        book.var(0, Var('A'));
+        book.weak(0, 0, Var('A'));
+        book.weak(2, 2, Var('A'));
+    }
+
+    #[test]
+    #[should_panic(expected = "the argument b must have type A, found B")]
+    fn appl_checks_argument_type() {
+        let mut book = Resolver::new();
+        let sort = book.sort();
+        let j = book
+            .start(
+                sort,
+                &[
+                    (Var('A'), Expr::Asterisk),
+                    (Var('B'), Expr::Asterisk),
+                    (Var('f'), "?x:(A).(A)".parse().unwrap()),
+                    (Var('b'), Var('B').into()),
+                ],
+            )
+            .unwrap();
+        let f = book.derive(j, &Var('f').into()).unwrap();
+        let b = book.derive(j, &Var('b').into()).unwrap();
+        book.appl(f, b);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not extend the base context: declaration 0 differs")]
+    fn form_requires_extended_context() {
+        let mut book = Resolver::new();
+
+        book.sort();
+        book.var(0, Var('A'));
+        book.weak(0, 0, Var('A'));
+        book.weak(0, 0, Var('B'));
+        book.var(3, Var('a'));
+        book.form(1, 4);
+    }
+
+    #[test]
+    fn abst_checks_codomain() {
+        let mut book = Resolver::new();
+
+        book.sort();
+        book.var(0, Var('A'));
+        book.var(1, Var('a'));
+        book.weak(1, 1, Var('a'));
+        book.form(1, 3);
+        let j = book.abst(2, 4);
+
+        assert_eq!(book.judgements[j].m, "$a:(A).(a)".parse().unwrap());
+        assert_eq!(book.judgements[j].n, "?a:(A).(A)".parse().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "but the codomain of")]
+    fn abst_rejects_unrelated_codomain() {
+        let mut book = Resolver::new();
+
+        book.sort();
+        book.var(0, Var('A'));
+        book.weak(0, 0, Var('A'));
+        book.var(2, Var('B'));
+        book.weak(1, 2, Var('B'));
+        book.var(4, Var('a'));
+        book.weak(3, 4, Var('a'));
+        book.form(4, 6);
+        book.abst(5, 7);
+    }
+
+    #[test]
+    fn context_mismatch() {
+        let mut book = Resolver::new();
+
+        let a = book.context.create(0, (Var('A'), Expr::Asterisk));
+        let b = book
+            .context
+            .create(a, (Var('u'), "?x:(A).(A)".parse().unwrap()));
+        let c = book
+            .context
+            .create(a, (Var('u'), "?y:(A).(A)".parse().unwrap()));
+        let d = book
+            .context
+            .create(a, (Var('u'), "?y:(A).(y)".parse().unwrap()));
+
+        assert!(book.same_context(b, c));
+        assert!(!book.same_context(a, b));
+        assert!(
+            book.context_mismatch(b, d)
+                .unwrap()
+                .starts_with("declaration 1 differs")
+        );
+    }
+
+    #[test]
+    fn alpha_equivalent_contexts_share_entries() {
+        let mut book = Resolver::new();
+
+        let a = book
+            .context
+            .create(0, (Var('u'), "?x:(*).(x)".parse().unwrap()));
+        let b = book
+            .context
+            .create(0, (Var('u'), "?y:(*).(y)".parse().unwrap()));
+        let c = book
+            .context
+            .create(0, (Var('v'), "?y:(*).(y)".parse().unwrap()));
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
 }
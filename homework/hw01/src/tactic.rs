@@ -0,0 +1,328 @@
+//! Goal-directed proofs.
+//!
+//! A [`Proof`] starts from a single goal and is refined by tactics until no
+//! goals remain. Each goal lives in a context of [`Resolver::context`], and
+//! every tactic that closes or splits a goal records a [`Step`], from which
+//! the proof term is assembled at the end.
+
+use std::collections::HashSet;
+
+use crate::{
+    book::Block,
+    model::{Application, Expr, Lambda, Var, generate_free_var_gte},
+    rule::Resolver,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Goal {
+    /// Index into [`Resolver::context`].
+    pub context: usize,
+    pub ty: Expr,
+}
+
+/// How a goal was solved, referring to the goals it was reduced to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Exact(Expr),
+    Intro(Var, Expr, usize),
+    Apply(Expr, Vec<usize>),
+}
+
+#[derive(Debug)]
+pub struct Proof<'r> {
+    resolver: &'r mut Resolver,
+    definitions: usize,
+    /// The type being proved, as stated; unfolding only changes the goal.
+    statement: Expr,
+    /// All goals ever created; the proof is of the first one.
+    goals: Vec<Goal>,
+    steps: Vec<Option<Step>>,
+    /// Unsolved goals, the current one first.
+    open: Vec<usize>,
+}
+
+impl<'r> Proof<'r> {
+    /// Starts a proof of `ty` in the environment and context of the judgement
+    /// `j`.
+    pub fn new(resolver: &'r mut Resolver, j: usize, ty: Expr) -> Result<Self> {
+        let judgement = &resolver.judgements[j];
+        let (definitions, context) = (judgement.definitions, judgement.context);
+        let anchor = resolver.anchor(definitions, context)?;
+        resolver.derive_type(anchor, &ty)?;
+
+        Ok(Self {
+            resolver,
+            definitions,
+            statement: ty.clone(),
+            goals: vec![Goal { context, ty }],
+            steps: vec![None],
+            open: vec![0],
+        })
+    }
+
+    pub fn resolver(&self) -> &Resolver {
+        self.resolver
+    }
+
+    /// Open goals, the current one first.
+    pub fn goals(&self) -> Vec<&Goal> {
+        self.open.iter().map(|&g| &self.goals[g]).collect()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.open.is_empty()
+    }
+
+    fn current(&self) -> Result<(usize, Goal)> {
+        let &g = self.open.first().ok_or("no goals left")?;
+        Ok((g, self.goals[g].clone()))
+    }
+
+    /// Solves the current goal by `step`, replacing it by `subgoals`.
+    fn solve(&mut self, step: Step, subgoals: Vec<Goal>) {
+        let g = self.open.remove(0);
+        let first = self.goals.len();
+        self.goals.extend(subgoals);
+        self.steps.resize(self.goals.len(), None);
+        self.open.splice(0..0, first..self.goals.len());
+        self.steps[g] = Some(step);
+    }
+
+    fn anchor(&mut self, context: usize) -> Result<usize> {
+        self.resolver.anchor(self.definitions, context)
+    }
+
+    /// Introduces the binder of a product goal as `name`, by default reusing
+    /// the binder's own name when it is fresh.
+    pub fn intro(&mut self, name: Option<Var>) -> Result<()> {
+        let (_, goal) = self.current()?;
+        let Expr::Pi(pi) = self.resolver.whnf(self.definitions, &goal.ty)? else {
+            return Err(format!("{} is not a product", goal.ty));
+        };
+
+        let declared = self
+            .resolver
+            .declarations(goal.context)
+            .into_iter()
+            .map(|(v, _)| v)
+            .collect::<HashSet<_>>();
+        let x = match name {
+            Some(x) if declared.contains(&x) => {
+                return Err(format!("{x} is already declared"));
+            }
+            Some(x) => x,
+            None => {
+                let mut avoid = declared;
+                avoid.extend(pi.2.free_vars());
+                avoid.remove(&pi.0);
+                generate_free_var_gte(&avoid, pi.0)?
+            }
+        };
+
+        // Check the binder type before extending the context, so a failing
+        // intro leaves no context entry behind.
+        let anchor = self.anchor(goal.context)?;
+        self.resolver.derive_type(anchor, &pi.1)?;
+        let context = self
+            .resolver
+            .context
+            .create(goal.context, (x, pi.1.clone()));
+        self.anchor(context)?;
+        let subgoal = Goal {
+            context,
+            ty: pi.2.alpha_substitution(pi.0, x.into())?,
+        };
+        let g = self.goals.len();
+        self.solve(Step::Intro(x, pi.1.clone(), g), vec![subgoal]);
+        Ok(())
+    }
+
+    /// Solves the current goal by `term`, whose type must be convertible to
+    /// the goal.
+    pub fn exact(&mut self, term: Expr) -> Result<()> {
+        let (_, goal) = self.current()?;
+        let anchor = self.anchor(goal.context)?;
+        let j = self.resolver.derive(anchor, &term)?;
+        self.resolver.convert(anchor, j, &goal.ty)?;
+        self.solve(Step::Exact(term), vec![]);
+        Ok(())
+    }
+
+    /// Solves the current goal by a declaration of its context.
+    pub fn assumption(&mut self) -> Result<()> {
+        let (_, goal) = self.current()?;
+        let found = self
+            .resolver
+            .declarations(goal.context)
+            .into_iter()
+            .rev()
+            .find(|(_, a)| self.resolver.convertible(self.definitions, a, &goal.ty));
+        let Some((x, _)) = found else {
+            return Err(format!("no assumption has type {}", goal.ty));
+        };
+        self.exact(x.into())
+    }
+
+    /// Applies `term` of type `A1 -> ... -> An -> B`, with `B` convertible to
+    /// the goal, leaving goals `A1`, ..., `An`.
+    pub fn apply(&mut self, term: Expr) -> Result<()> {
+        let (_, goal) = self.current()?;
+        let anchor = self.anchor(goal.context)?;
+        let j = self.resolver.derive(anchor, &term)?;
+
+        let mut ty = self.resolver.judgements[j].n.clone();
+        let mut premises = vec![];
+        while !self.resolver.convertible(self.definitions, &ty, &goal.ty) {
+            let Expr::Pi(pi) = self.resolver.whnf(self.definitions, &ty)? else {
+                return Err(format!(
+                    "{term} cannot be applied: no conclusion matches {}",
+                    goal.ty
+                ));
+            };
+            if pi.2.free_vars().contains(&pi.0) {
+                return Err(format!(
+                    "{term} cannot be applied: the argument for {} cannot be inferred",
+                    pi.0
+                ));
+            }
+            premises.push(pi.1.clone());
+            ty = pi.2.clone();
+        }
+
+        let subgoals = premises
+            .into_iter()
+            .map(|ty| Goal {
+                context: goal.context,
+                ty,
+            })
+            .collect::<Vec<_>>();
+        let first = self.goals.len();
+        let args = (first..first + subgoals.len()).collect();
+        self.solve(Step::Apply(term, args), subgoals);
+        Ok(())
+    }
+
+    /// Unfolds definitions at the head of the current goal.
+    pub fn unfold(&mut self) -> Result<()> {
+        let (g, goal) = self.current()?;
+        let ty = self.resolver.whnf(self.definitions, &goal.ty)?;
+        if ty == goal.ty {
+            return Err(format!("{} cannot be unfolded", goal.ty));
+        }
+        self.goals[g].ty = ty;
+        Ok(())
+    }
+
+    fn term_of(&self, g: usize) -> Result<Expr> {
+        Ok(match &self.steps[g] {
+            None => return Err(format!("goal {} is unsolved", self.goals[g].ty)),
+            Some(Step::Exact(term)) => term.clone(),
+            Some(Step::Intro(x, a, body)) => Lambda(*x, a.clone(), self.term_of(*body)?).into(),
+            Some(Step::Apply(f, args)) => args.iter().try_fold(f.clone(), |f, &a| {
+                Ok::<_, String>(Application(f, self.term_of(a)?).into())
+            })?,
+        })
+    }
+
+    /// The proof term, once all goals are solved.
+    pub fn term(&self) -> Result<Expr> {
+        if !self.is_done() {
+            return Err(format!("unsolved goals remain: {}", self.open.len()));
+        }
+        self.term_of(0)
+    }
+
+    /// The finished proof as a def2 block named `name`, with the context of
+    /// the initial goal as parameters.
+    pub fn block(&self, name: String) -> Result<Block> {
+        Ok(Block {
+            context: self.resolver.declarations(self.goals[0].context),
+            name,
+            m: Some(self.term()?),
+            n: self.statement.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::book::Book;
+
+    use super::*;
+    use crate::fixtures::propositions;
+
+    #[test]
+    fn and_commutes() {
+        let (mut resolver, j) = propositions();
+        let goal: Expr = "implies[(and[(A),(B)]),(and[(B),(A)])]".parse().unwrap();
+
+        let mut proof = Proof::new(&mut resolver, j, goal.clone()).unwrap();
+        proof.intro(Some(Var('p'))).unwrap();
+        proof
+            .exact("%(%(p)(A))($a:(A).($b:(B).(a)))".parse().unwrap())
+            .unwrap_err();
+        proof.unfold().unwrap();
+        proof.intro(Some(Var('C'))).unwrap();
+        proof.intro(Some(Var('w'))).unwrap();
+        proof.apply("%(p)(C)".parse().unwrap()).unwrap();
+        proof.intro(Some(Var('a'))).unwrap();
+        proof.intro(Some(Var('b'))).unwrap();
+        assert_eq!(proof.goals().len(), 1);
+        proof.apply("w".parse().unwrap()).unwrap();
+        assert_eq!(proof.goals().len(), 2);
+        proof.assumption().unwrap();
+        proof.exact("a".parse().unwrap()).unwrap();
+        assert!(proof.is_done());
+
+        let block = proof.block("and_comm".to_string()).unwrap();
+        assert_eq!(block.context.len(), 2);
+        let mut book: Book = include_str!("../check/def2").parse().unwrap();
+        book.0.push(block);
+        book.check().unwrap();
+    }
+
+    #[test]
+    fn block_keeps_statement() {
+        let (mut resolver, j) = propositions();
+        let goal: Expr = "implies[(A),(A)]".parse().unwrap();
+
+        let mut proof = Proof::new(&mut resolver, j, goal.clone()).unwrap();
+        proof.unfold().unwrap();
+        proof.intro(None).unwrap();
+        proof.assumption().unwrap();
+
+        assert_eq!(proof.block("id".to_string()).unwrap().n, goal);
+    }
+
+    #[test]
+    fn errors() {
+        let (mut resolver, j) = propositions();
+        let mut proof = Proof::new(&mut resolver, j, "?x:(A).(A)".parse().unwrap()).unwrap();
+
+        assert!(
+            proof
+                .intro(Some(Var('A')))
+                .unwrap_err()
+                .contains("already declared")
+        );
+        assert!(proof.assumption().unwrap_err().contains("no assumption"));
+        assert!(
+            proof
+                .term()
+                .unwrap_err()
+                .contains("unsolved goals remain: 1")
+        );
+        proof.intro(None).unwrap();
+        assert!(
+            proof
+                .apply("B".parse().unwrap())
+                .unwrap_err()
+                .contains("cannot be applied")
+        );
+        proof.assumption().unwrap();
+        assert!(proof.intro(None).unwrap_err().contains("no goals left"));
+        assert_eq!(proof.term().unwrap(), "$x:(A).(x)".parse().unwrap());
+    }
+}
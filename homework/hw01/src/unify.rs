@@ -0,0 +1,279 @@
+//! Metavariables and first-order unification.
+//!
+//! Unification works on [`de_bruijn::Expr`], so bound variables are compared
+//! up to renaming. Solutions may only mention free variables of the context
+//! the metavariables were created in, never variables bound inside the
+//! unified terms. Definitions are unfolded when the terms do not match
+//! syntactically.
+//!
+//! Unification is first-order: a metavariable is only solved by the term it
+//! is matched against, so an applied metavariable like `%(_0)(x)` never
+//! unifies with a term that is not itself an application. It is used to infer
+//! the omitted *leading* arguments of definition instances from the types of
+//! the explicit ones; arguments that no explicit argument determines are
+//! reported as not inferable.
+
+use crate::{
+    de_bruijn::{self, Application, Definition, Lambda, Pi},
+    model::{Expr, Var},
+    rule::Resolver,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+#[derive(Debug, Clone, Default)]
+pub struct Metas {
+    solutions: Vec<Option<de_bruijn::Expr>>,
+}
+
+impl Metas {
+    pub fn fresh(&mut self) -> Expr {
+        self.solutions.push(None);
+        Expr::Meta(self.solutions.len() - 1)
+    }
+
+    pub fn solution(&self, m: usize) -> Option<&de_bruijn::Expr> {
+        self.solutions.get(m)?.as_ref()
+    }
+
+    /// Replaces every solved metavariable in `e` by its solution.
+    pub fn instantiate(&self, e: &de_bruijn::Expr) -> de_bruijn::Expr {
+        use de_bruijn::Expr as E;
+        match e {
+            E::Meta(m) => match self.solution(*m) {
+                Some(s) => self.instantiate(s),
+                None => e.clone(),
+            },
+            E::Var(_) | E::Asterisk | E::Square | E::Hole(_) => e.clone(),
+            E::Lambda(l) => E::Lambda(Box::new(Lambda(
+                l.0,
+                self.instantiate(&l.1),
+                self.instantiate(&l.2),
+            ))),
+            E::Pi(pi) => E::Pi(Box::new(Pi(
+                pi.0,
+                self.instantiate(&pi.1),
+                self.instantiate(&pi.2),
+            ))),
+            E::Definition(d) => E::Definition(Definition(
+                d.0.clone(),
+                d.1.iter().map(|e| self.instantiate(e)).collect(),
+            )),
+            E::Application(a) => E::Application(Box::new(Application(
+                self.instantiate(&a.0),
+                self.instantiate(&a.1),
+            ))),
+        }
+    }
+
+    fn occurs(m: usize, e: &de_bruijn::Expr) -> bool {
+        use de_bruijn::Expr as E;
+        match e {
+            E::Meta(n) => *n == m,
+            E::Var(_) | E::Asterisk | E::Square | E::Hole(_) => false,
+            E::Lambda(l) => Self::occurs(m, &l.1) || Self::occurs(m, &l.2),
+            E::Pi(pi) => Self::occurs(m, &pi.1) || Self::occurs(m, &pi.2),
+            E::Definition(d) => d.1.iter().any(|e| Self::occurs(m, e)),
+            E::Application(a) => Self::occurs(m, &a.0) || Self::occurs(m, &a.1),
+        }
+    }
+
+    fn assign(&mut self, m: usize, e: &de_bruijn::Expr) -> bool {
+        if Self::occurs(m, e) || e.has_loose_indices() {
+            return false;
+        }
+        self.solutions[m] = Some(e.clone());
+        true
+    }
+
+    /// Tries to make `a` and `b` equal by solving metavariables, unfolding
+    /// definitions through `delta`. Solutions are only kept on success.
+    pub fn unify(
+        &mut self,
+        a: &de_bruijn::Expr,
+        b: &de_bruijn::Expr,
+        delta: &impl Fn(&Definition) -> Option<de_bruijn::Expr>,
+    ) -> bool {
+        let saved = self.solutions.clone();
+        let unified = self.unify_in(a, b, delta);
+        if !unified {
+            self.solutions = saved;
+        }
+        unified
+    }
+
+    fn unify_in(
+        &mut self,
+        a: &de_bruijn::Expr,
+        b: &de_bruijn::Expr,
+        delta: &impl Fn(&Definition) -> Option<de_bruijn::Expr>,
+    ) -> bool {
+        use de_bruijn::Expr as E;
+        let (a, b) = (self.instantiate(a), self.instantiate(b));
+
+        let saved = self.solutions.clone();
+        let structural = match (&a, &b) {
+            (E::Meta(m), E::Meta(n)) if m == n => return true,
+            (E::Meta(m), e) | (e, E::Meta(m)) => return self.assign(*m, e),
+            (E::Var(x), E::Var(y)) => x == y,
+            (E::Asterisk, E::Asterisk) | (E::Square, E::Square) => true,
+            (E::Lambda(l1), E::Lambda(l2)) => {
+                return self.unify(&l1.1, &l2.1, delta) && self.unify(&l1.2, &l2.2, delta);
+            }
+            (E::Pi(p1), E::Pi(p2)) => {
+                return self.unify(&p1.1, &p2.1, delta) && self.unify(&p1.2, &p2.2, delta);
+            }
+            (E::Definition(d1), E::Definition(d2)) => {
+                d1.0 == d2.0
+                    && d1.1.len() == d2.1.len()
+                    && d1.1.iter().zip(&d2.1).all(|(x, y)| self.unify(x, y, delta))
+            }
+            (E::Application(a1), E::Application(a2)) => {
+                self.unify(&a1.0, &a2.0, delta) && self.unify(&a1.1, &a2.1, delta)
+            }
+            _ => false,
+        };
+        if structural {
+            return true;
+        }
+        self.solutions = saved;
+
+        let (a2, b2) = (a.whnf_with(delta), b.whnf_with(delta));
+        (a2 != a || b2 != b) && self.unify(&a2, &b2, delta)
+    }
+}
+
+impl Resolver {
+    /// Fills in the omitted leading arguments of an instance of `name` with
+    /// parameters `params`, inferring them from the types of the `explicit`
+    /// arguments. Explicit arguments with holes determine nothing and are
+    /// returned unchanged.
+    pub(crate) fn implicit_arguments(
+        &mut self,
+        anchor: usize,
+        name: &str,
+        params: &[(Var, Expr)],
+        explicit: &[Expr],
+    ) -> Result<Vec<Expr>> {
+        let definitions = self.judgements[anchor].definitions;
+        let implicit = params.len() - explicit.len();
+        let mut metas = Metas::default();
+        let args = (0..implicit)
+            .map(|_| metas.fresh())
+            .chain(explicit.iter().cloned())
+            .collect::<Vec<_>>();
+
+        let mut substitution = vec![];
+        for (i, ((v, ty), u)) in params.iter().zip(&args).enumerate() {
+            if u.has_holes() {
+                substitution.push((*v, metas.fresh()));
+            } else if i >= implicit {
+                let j = self.derive(anchor, u)?;
+                let actual = self.judgements[j].n.de_bruijn();
+                let expected = ty.substitute(&substitution)?.de_bruijn();
+                let delta = |d: &Definition| self.unfold(definitions, d);
+                if !metas.unify(&actual, &expected, &delta) {
+                    let expected: Expr = metas.instantiate(&expected).try_into()?;
+                    return Err(format!(
+                        "the argument {u} of {name} has type {}, expected {expected}",
+                        self.judgements[j].n
+                    ));
+                }
+                substitution.push((*v, self.judgements[j].m.clone()));
+            } else {
+                substitution.push((*v, u.clone()));
+            }
+        }
+
+        params
+            .iter()
+            .zip(args)
+            .map(|((v, _), u)| {
+                if u.has_holes() {
+                    return Ok(u);
+                }
+                let u: Expr = metas.instantiate(&u.de_bruijn()).try_into()?;
+                if u.has_metas() {
+                    Err(format!("cannot infer the argument for {v} of {name}"))
+                } else {
+                    Ok(u)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::fixtures::def2;
+
+    #[rstest]
+    #[case("_0", "?x:(A).(B)", true)]
+    #[case("?x:(_0).(_1)", "?y:(A).(B)", true)]
+    #[case("?x:(_0).(x)", "?y:(A).(A)", false)]
+    #[case("?x:(*).(_0)", "?y:(*).(y)", false)]
+    #[case("%(_0)(_0)", "%(A)(B)", false)]
+    #[case("_0", "%(_0)(A)", false)]
+    #[case("%(_0)(A)", "%(B)(A)", true)]
+    #[case("%(_0)(A)", "B", false)]
+    fn unify(#[case] a: &str, #[case] b: &str, #[case] unifies: bool) {
+        let mut metas = Metas::default();
+        metas.fresh();
+        metas.fresh();
+        let parse = |s: &str| -> de_bruijn::Expr {
+            let s = s.replace("_0", "M").replace("_1", "N");
+            let e: Expr = s.parse().unwrap();
+            e.de_bruijn().substitute(&[
+                (Var('M'), de_bruijn::Expr::Meta(0)),
+                (Var('N'), de_bruijn::Expr::Meta(1)),
+            ])
+        };
+        let (a, b) = (parse(a), parse(b));
+        assert_eq!(metas.unify(&a, &b, &|_| None), unifies);
+        if unifies {
+            assert_eq!(metas.instantiate(&a), metas.instantiate(&b));
+        } else {
+            assert_eq!(metas.solution(0), None);
+        }
+    }
+
+    #[rstest]
+    #[case("implies_el[(f),(a)]", "implies_el[(A),(B),(f),(a)]")]
+    #[case("implies_el[(g),(a)]", "implies_el[(A),(B),(g),(a)]")]
+    #[case("implies_el[(B),(g),(a)]", "implies_el[(A),(B),(g),(a)]")]
+    #[case("and_in[(a),(b)]", "and_in[(A),(B),(a),(b)]")]
+    #[case("contra_el[(B),(c)]", "contra_el[(B),(c)]")]
+    fn implicit_arguments(#[case] e: Expr, #[case] expected: Expr) {
+        let (mut resolver, anchor) = def2(&[
+            ('A', "*"),
+            ('B', "*"),
+            ('a', "A"),
+            ('b', "B"),
+            ('c', "contra[]"),
+            ('f', "implies[(A),(B)]"),
+            ('g', "?x:(A).(B)"),
+        ]);
+
+        let j = resolver.derive(anchor, &e).unwrap();
+        assert_eq!(resolver.judgements[j].m, expected);
+    }
+
+    #[rstest]
+    #[case("implies_el[(a)]", "cannot infer the argument for B")]
+    #[case("implies_el[(f),(b)]", "of implies_el has type")]
+    fn implicit_argument_errors(#[case] e: Expr, #[case] message: &str) {
+        let (mut resolver, anchor) = def2(&[
+            ('A', "*"),
+            ('B', "*"),
+            ('a', "A"),
+            ('b', "B"),
+            ('f', "implies[(A),(B)]"),
+        ]);
+
+        let error = resolver.derive(anchor, &e).unwrap_err();
+        assert!(error.contains(message), "{error}");
+    }
+}
@@ -1,39 +1,94 @@
-use crate::{model::Var, rule::Resolver};
-
+use crate::{
+    model::{Expr, Var},
+    rule::Resolver,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+/// Checks derivation logs. Derived rules may record several judgements for a
+/// single log line, so `lines` maps every line to the judgement it derived.
+///
+/// The derived `weaken`, `start` and `instw` rules replace the `weak`, `cp`
+/// and `sp` chains that rebuild contexts, and `derive j e` derives `e` in the
+/// context of `j` in one step. With `start` and `derive`, a definition takes
+/// a few lines: `check/log_derived` takes 12 steps where `check/log` takes
+/// 146.
 #[derive(Debug, Default)]
 pub struct Verifier {
     resolver: Resolver,
+    lines: Vec<usize>,
 }
 
-struct Tokenizer<T>(T);
+struct Tokenizer<'l, T>(T, &'l [usize]);
+
+/// `inst` refers to a definition either by its position on the definition
+/// path or by name.
+enum DefinitionRef {
+    Index(usize),
+    Name(String),
+}
+
+impl<'a, T: Iterator<Item = &'a str>> Tokenizer<'_, T> {
+    fn token(&mut self, what: &str) -> Result<&'a str> {
+        self.0.next().ok_or_else(|| format!("expected {what}"))
+    }
+
+    fn number(&mut self, what: &str) -> Result<usize> {
+        let token = self.token(what)?;
+        token
+            .parse()
+            .map_err(|_| format!("expected {what}, found {token}"))
+    }
 
-impl<'a, T: Iterator<Item = &'a str>> Tokenizer<T> {
-    pub fn line_number(&mut self) -> usize {
-        self.0.next().expect("line number").parse().unwrap()
+    pub fn line_number(&mut self) -> Result<usize> {
+        self.number("line number")
     }
 
-    pub fn judgement(&mut self) -> usize {
-        self.0.next().expect("judgement index").parse().unwrap()
+    pub fn judgement(&mut self) -> Result<usize> {
+        let line = self.number("judgement index")?;
+        self.1
+            .get(line)
+            .copied()
+            .ok_or_else(|| format!("judgement index {line} refers to a later line"))
     }
 
-    pub fn take_usize(&mut self, reason: &str) -> usize {
-        self.0.next().expect(reason).parse().unwrap()
+    pub fn take_usize(&mut self, reason: &str) -> Result<usize> {
+        self.number(reason)
     }
 
-    pub fn instruction(&mut self) -> &'a str {
-        self.0.next().expect("instruction")
+    pub fn instruction(&mut self) -> Result<&'a str> {
+        self.token("instruction")
     }
 
-    pub fn variable(&mut self) -> Var {
-        Var(self.0.next().expect("variable name").parse().unwrap())
+    pub fn variable(&mut self) -> Result<Var> {
+        let token = self.token("variable name")?;
+        token
+            .parse()
+            .map(Var)
+            .map_err(|_| format!("expected a variable name, found {token}"))
     }
 
-    pub fn constant(&mut self) -> String {
-        self.0.next().expect("constant name").to_string()
+    pub fn constant(&mut self) -> Result<String> {
+        self.token("constant name").map(str::to_string)
     }
 
-    pub fn end(mut self) {
-        assert_eq!(self.0.next(), None);
+    pub fn definition(&mut self) -> Result<DefinitionRef> {
+        let token = self.token("definition index or name")?;
+        Ok(match token.parse() {
+            Ok(ix) => DefinitionRef::Index(ix),
+            Err(_) => DefinitionRef::Name(token.to_string()),
+        })
+    }
+
+    pub fn expression(&mut self) -> Result<Expr> {
+        self.token("expression")?.parse()
+    }
+
+    pub fn end(mut self) -> Result<()> {
+        match self.0.next() {
+            None => Ok(()),
+            Some(token) => Err(format!("unexpected trailing input {token}")),
+        }
     }
 }
 
@@ -41,117 +96,212 @@ impl Verifier {
     pub fn new() -> Self {
         Self {
             resolver: Resolver::new(),
+            lines: vec![],
         }
     }
 
-    pub fn run(input: &str) {
+    pub fn run(input: &str) -> Self {
         let mut v = Self::new();
 
         for line in input.lines() {
             if line == "-1" {
                 break;
             }
-            v.run_line(line);
+            eprint!("verifying `{line}`...");
+            if let Err(e) = v.run_line(line) {
+                panic!("{e}");
+            }
+            eprintln!("ok");
+        }
+
+        v
+    }
+
+    /// Checks `input` like [`Verifier::run`], but without tracing, and
+    /// reports the first line that does not check as an error.
+    pub fn try_run(input: &str) -> Result<Self> {
+        let mut v = Self::new();
+
+        for (i, line) in input.lines().enumerate() {
+            if line == "-1" {
+                break;
+            }
+            v.run_line(line)
+                .map_err(|e| format!("line {}: {e}", i + 1))?;
         }
+
+        Ok(v)
+    }
+
+    pub fn resolver(&self) -> &Resolver {
+        &self.resolver
     }
 
-    pub fn run_line(&mut self, line: &str) {
-        eprint!("verifying `{line}`...");
-        let mut t = Tokenizer(line.split(' '));
-        let lineno = t.line_number();
+    pub fn into_resolver(self) -> Resolver {
+        self.resolver
+    }
 
-        assert_eq!(lineno, self.resolver.judgements.len(), "wrong line number");
+    /// Checks one log line and returns the judgement it derived. A line that
+    /// does not check leaves the verifier unchanged.
+    pub fn run_line(&mut self, line: &str) -> Result<usize> {
+        let len = self.resolver.judgements.len();
+        let result = self.step(line);
+        match result {
+            Ok(j) => self.lines.push(j),
+            Err(_) => self.resolver.truncate(len),
+        }
+        result
+    }
 
-        let op_name = t.instruction();
+    fn step(&mut self, line: &str) -> Result<usize> {
+        let mut t = Tokenizer(line.split(' '), &self.lines);
+        let lineno = t.line_number()?;
 
-        match op_name {
+        if lineno != self.lines.len() {
+            return Err(format!(
+                "wrong line number {lineno}, expected {}",
+                self.lines.len()
+            ));
+        }
+
+        let op_name = t.instruction()?;
+
+        let j = match op_name {
             "sort" => self.resolver.sort(),
             "var" => {
-                let j = t.judgement();
+                let j = t.judgement()?;
 
-                let var = t.variable();
+                let var = t.variable()?;
 
-                self.resolver.var(j, var)
+                self.resolver.try_var(j, var)?
             }
             "weak" => {
-                let a = t.judgement();
-                let b = t.judgement();
-                let var = t.variable();
+                let a = t.judgement()?;
+                let b = t.judgement()?;
+                let var = t.variable()?;
 
-                self.resolver.weak(a, b, var)
+                self.resolver.try_weak(a, b, var)?
             }
             "form" => {
-                let a = t.judgement();
-                let b = t.judgement();
+                let a = t.judgement()?;
+                let b = t.judgement()?;
 
-                self.resolver.form(a, b)
+                self.resolver.try_form(a, b)?
             }
             "appl" => {
-                let a = t.judgement();
-                let b = t.judgement();
+                let a = t.judgement()?;
+                let b = t.judgement()?;
 
-                self.resolver.appl(a, b)
+                self.resolver.try_appl(a, b)?
             }
             "abst" => {
-                let a = t.judgement();
-                let b = t.judgement();
+                let a = t.judgement()?;
+                let b = t.judgement()?;
 
-                self.resolver.abst(a, b)
+                self.resolver.try_abst(a, b)?
             }
             "conv" => {
-                let a = t.judgement();
-                let b = t.judgement();
+                let a = t.judgement()?;
+                let b = t.judgement()?;
 
-                self.resolver.conv(a, b)
+                self.resolver.try_conv(a, b)?
             }
             "def" => {
-                let a = t.judgement();
-                let b = t.judgement();
+                let a = t.judgement()?;
+                let b = t.judgement()?;
 
-                let name = t.constant();
+                let name = t.constant()?;
 
-                self.resolver.def(a, b, name)
+                self.resolver.try_def(a, b, name)?
             }
             "defpr" => {
-                let a = t.judgement();
-                let b = t.judgement();
+                let a = t.judgement()?;
+                let b = t.judgement()?;
 
-                let name = t.constant();
+                let name = t.constant()?;
 
-                self.resolver.def_prim(a, b, name)
+                self.resolver.try_def_prim(a, b, name)?
             }
             "inst" => {
-                let m = t.judgement();
-                let n = t.take_usize("arity");
+                let m = t.judgement()?;
+                let n = t.take_usize("arity")?;
 
                 let mut args = Vec::with_capacity(n);
 
                 for _ in 0..n {
-                    args.push(t.judgement());
+                    args.push(t.judgement()?);
                 }
 
-                let definition = t.take_usize("definition index");
-
-                self.resolver.inst_ix(m, &args, definition)
+                match t.definition()? {
+                    DefinitionRef::Index(d) => self.resolver.try_inst_ix(m, &args, d)?,
+                    DefinitionRef::Name(name) => self.resolver.try_inst(m, &args, name)?,
+                }
             }
             "cp" => {
-                let a = t.judgement();
+                let a = t.judgement()?;
 
                 self.resolver.cp(a)
             }
             "sp" => {
-                let a = t.judgement();
+                let a = t.judgement()?;
 
-                let ix = t.take_usize("sp index");
+                let ix = t.take_usize("sp index")?;
 
-                self.resolver.sp(a, ix)
+                self.resolver.try_sp(a, ix)?
             }
-            i => panic!("Unknown instruction {i}"),
-        };
+            "weaken" => {
+                let a = t.judgement()?;
+                let b = t.judgement()?;
 
-        t.end();
+                let context = self.resolver.judgements[b].context;
+                self.resolver.weak_to(a, context)?
+            }
+            "start" => {
+                let a = t.judgement()?;
+                let n = t.take_usize("number of declarations")?;
+
+                let mut declarations = Vec::with_capacity(n);
 
-        eprintln!("ok");
+                for _ in 0..n {
+                    let var = t.variable()?;
+                    declarations.push((var, t.expression()?));
+                }
+
+                self.resolver.start(a, &declarations)?
+            }
+            "instw" => {
+                let m = t.judgement()?;
+                let n = t.take_usize("arity")?;
+
+                let mut args = Vec::with_capacity(n);
+
+                for _ in 0..n {
+                    args.push(t.judgement()?);
+                }
+
+                let definition = match t.definition()? {
+                    DefinitionRef::Index(d) => d,
+                    DefinitionRef::Name(name) => {
+                        let definitions = self.resolver.judgements[m].definitions;
+                        self.resolver
+                            .definition_index(definitions, &name)
+                            .ok_or_else(|| format!("unknown definition {name}"))?
+                    }
+                };
+
+                self.resolver.inst_weak(m, &args, definition)?
+            }
+            "derive" => {
+                let a = t.judgement()?;
+                let e = t.expression()?;
+
+                self.resolver.derive(a, &e)?
+            }
+            i => return Err(format!("Unknown instruction {i}")),
+        };
+
+        t.end()?;
+        Ok(j)
     }
 }
 
@@ -159,6 +309,8 @@ impl Verifier {
 mod tests {
     use std::io::{BufRead, BufReader};
 
+    use rstest::rstest;
+
     use super::*;
 
     #[test]
@@ -168,6 +320,91 @@ mod tests {
         Verifier::run(input);
     }
 
+    #[test]
+    pub fn check_inst_by_name() {
+        let names = ["implies", "and"];
+        let input = include_str!("../check/log")
+            .lines()
+            .map(|line| match line.split_once(" inst ") {
+                Some(_) => {
+                    let (rest, d) = line.rsplit_once(' ').unwrap();
+                    format!("{rest} {}", names[d.parse::<usize>().unwrap()])
+                }
+                None => line.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        assert!(input.contains("inst 28 2 34 35 implies"));
+        Verifier::run(&input);
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown definition or")]
+    pub fn inst_unknown_name() {
+        Verifier::run("0 sort\n1 def 0 0 implies\n2 inst 1 0 or");
+    }
+
+    #[test]
+    pub fn check_derived() {
+        let full = Verifier::run(include_str!("../check/log"));
+        let short = Verifier::run(include_str!("../check/log_derived"));
+        assert_eq!(full.lines.len(), 146);
+        assert_eq!(short.lines.len(), 12);
+
+        let definitions = |v: &Verifier| {
+            let r = v.resolver();
+            let last = r.judgements.last().unwrap().definitions;
+            r.definitions
+                .path(last)
+                .into_iter()
+                .map(|d| {
+                    (
+                        d.name.clone(),
+                        d.m.clone(),
+                        d.n.clone(),
+                        r.declarations(d.context),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let (full, short) = (definitions(&full), definitions(&short));
+        assert_eq!(full.len(), short.len());
+        for (f, s) in full.iter().zip(&short) {
+            assert_eq!(f.0, s.0);
+            assert!(f.1.as_ref().unwrap().alpha_eq(s.1.as_ref().unwrap()));
+            assert!(f.2.alpha_eq(&s.2));
+            assert_eq!(f.3, s.3);
+        }
+    }
+
+    #[rstest]
+    #[case("0 sort\n1 var 0 A\n-1", Ok(2))]
+    #[case("0 sort\n1 var 0 A\n2 var 1 A\n-1", Err("line 3: "))]
+    #[case("0 sort\n1 frob 0\n", Err("line 2: Unknown instruction frob"))]
+    pub fn try_run(#[case] input: &str, #[case] expected: std::result::Result<usize, &str>) {
+        match (Verifier::try_run(input), expected) {
+            (Ok(v), Ok(n)) => assert_eq!(v.lines.len(), n),
+            (Err(e), Err(prefix)) => assert!(e.starts_with(prefix), "{e}"),
+            (result, _) => panic!("unexpected {:?}", result.map(|v| v.lines)),
+        }
+    }
+
+    #[test]
+    pub fn failed_line_leaves_verifier_unchanged() {
+        let mut v = Verifier::new();
+        v.run_line("0 sort").unwrap();
+        v.run_line("1 var 0 A").unwrap();
+
+        let error = v.run_line("2 start 1 2 x A A A").unwrap_err();
+        assert!(error.contains("is not fresh"), "{error}");
+        assert_eq!(v.resolver().judgements.len(), 2);
+        assert_eq!(v.resolver().rules.len(), 2);
+
+        assert_eq!(v.run_line("2 var 1 x"), Ok(2));
+    }
+
     #[test]
     pub fn check2() {
         let input_path = "../hw01/check/bez_rules";
@@ -180,7 +417,7 @@ mod tests {
             if line == "-1" {
                 break;
             }
-            v.run_line(&line);
+            v.run_line(&line).unwrap();
         }
     }
 }